@@ -0,0 +1,67 @@
+//! Builds the shortcuts of a location with 2000 projects. First with an empty icon cache, then with a full one.
+//!
+//! Run with `cargo bench --bench large_location`
+use std::{path::Path, process::Command, time::Instant};
+
+const GROUPS: usize = 40;
+const PROJECTS_PER_GROUP: usize = 50;
+
+fn main() {
+    if !cfg!(target_os = "linux") {
+        eprintln!("Skipped. The config and data directories can only be moved on Linux");
+        return;
+    }
+    let root = tempfile::tempdir().unwrap();
+    let code = root.path().join("code");
+    for group in 0..GROUPS {
+        for project in 0..PROJECTS_PER_GROUP {
+            let project = code.join(format!("group{group}/project{project}"));
+            std::fs::create_dir_all(&project).unwrap();
+            std::fs::write(project.join("Cargo.toml"), "").unwrap();
+        }
+    }
+    let home = root.path().join("home");
+    let shortcuts = root.path().join("shortcuts");
+    write_config(&home, &code, &shortcuts);
+
+    for cache in ["an empty", "a full"] {
+        let start = Instant::now();
+        let status = Command::new(env!("CARGO_BIN_EXE_auto_project"))
+            .arg("build-shortcuts")
+            .env("HOME", &home)
+            .env("XDG_CONFIG_HOME", home.join(".config"))
+            .env("XDG_DATA_HOME", home.join(".local/share"))
+            .env("XDG_CACHE_HOME", home.join(".cache"))
+            .status()
+            .unwrap();
+        let elapsed = start.elapsed();
+        assert!(status.success(), "build-shortcuts failed");
+        let count = std::fs::read_dir(&shortcuts).unwrap().count();
+        assert_eq!(count, GROUPS * PROJECTS_PER_GROUP);
+        println!("Built {count} shortcuts with {cache} icon cache in {elapsed:?}");
+    }
+}
+/// Writes shortcuts to a directory. VS Code is this executable so the editor check passes
+fn write_config(home: &Path, code: &Path, shortcuts: &Path) {
+    let path = |path: &Path| toml::Value::from(path.to_string_lossy().as_ref()).to_string();
+    let editor = std::env::current_exe().unwrap();
+    let config = format!(
+        r#"vs_code_path = {editor}
+
+[[project_locations]]
+path = {code}
+min_depth = 2
+max_depth = 2
+
+[shortcut_backend]
+type = "LnkDirectory"
+path = {shortcuts}
+"#,
+        editor = path(&editor),
+        code = path(code),
+        shortcuts = path(shortcuts),
+    );
+    let config_dir = home.join(".config/auto_project");
+    std::fs::create_dir_all(&config_dir).unwrap();
+    std::fs::write(config_dir.join("config.toml"), config).unwrap();
+}
@@ -6,6 +6,9 @@ use log::info;
 
 use crate::{
     config::{IconStyle, Project, ProjectLocation},
+    dry_run::{print_table, OutputFormat},
+    editor::{self, DetectedEditor},
+    icon::{self, IconRequest},
     utils::GetConfig,
     AppState,
 };
@@ -15,6 +18,12 @@ use crate::{
 pub struct AutoProject {
     #[clap(subcommand)]
     pub command: Command,
+    /// Print what would be changed without writing anything
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+    /// The output format of --dry-run
+    #[arg(long, global = true, value_enum, default_value_t)]
+    pub format: OutputFormat,
 }
 
 #[derive(Subcommand, Debug)]
@@ -26,27 +35,49 @@ pub enum Command {
     AddDisabledProject {
         path: Option<PathBuf>,
     },
+    /// Adds a glob pattern that is excluded from every project location
+    AddExcludePattern {
+        pattern: String,
+    },
     SetVSCodePath {
         path: PathBuf,
     },
     /// Adds a new project to the config
     AddProject(AddProject),
+    /// Manages the generated icons
+    #[command(subcommand)]
+    Icons(IconsCommand),
+    /// Lists the editors installed on this machine
+    DetectEditors(DetectEditors),
+}
+#[derive(Subcommand, Debug)]
+pub enum IconsCommand {
+    /// Removes icons that are no longer used by any project
+    Gc,
 }
 #[derive(Args, Debug)]
 pub struct AddProject {
     #[arg(short, long)]
     name: Option<String>,
+    /// Renders an icon in this style for the project
     #[arg(short, long)]
     icon_style: Option<IconStyle>,
-    /// Currently Not Implemented
-    ///
+    /// Renders an icon from a template in the templates directory. Replaces --icon-style
+    #[arg(long)]
+    icon_template: Option<String>,
     /// Will use a specified icon instead of the default
-    /// Must be SVG
-    /// .ico files are allowed on Windows
+    ///
+    /// Can be an SVG, PNG or ICO file. SVG and PNG files are converted to ICO
     #[arg(long)]
     icon_path: Option<PathBuf>,
     #[arg(short, long)]
     description: Option<String>,
+    /// The id of the editor the project is opened in
+    #[arg(long)]
+    editor: Option<String>,
+    /// Open the directory even if the project has a .code-workspace file
+    #[arg(long)]
+    no_workspace: bool,
     /// If not provided, the current directory will be used
     #[arg(short, long)]
     path: Option<PathBuf>,
@@ -55,10 +86,18 @@ impl AddProject {
     pub fn execute(self, app_state: &mut AppState) -> anyhow::Result<Project> {
         let Self {
             name,
+            icon_style,
+            icon_template,
+            icon_path,
             description,
+            editor,
+            no_workspace,
             path,
-            ..
         } = self;
+        let open_workspace = no_workspace.then_some(false);
+        if let Some(editor) = &editor {
+            app_state.config.validate_editor(editor)?;
+        }
 
         let path = if let Some(path) = path {
             path
@@ -74,6 +113,22 @@ impl AddProject {
         } else {
             path.file_name().unwrap().to_string_lossy().to_string()
         };
+        let icon = if let Some(icon_path) = icon_path {
+            Some(icon::import_icon(&icon_path, &name, app_state)?)
+        } else if icon_template.is_some() || icon_style.is_some() {
+            let template = icon_template.unwrap_or_else(|| {
+                let style = icon_style.unwrap_or_default().resolve(&path);
+                style.template().to_string()
+            });
+            let request = IconRequest {
+                template,
+                name: name.clone(),
+                location: None,
+            };
+            Some(icon::build_icon(&request, &name, app_state)?)
+        } else {
+            None
+        };
         let AppState {
             config,
             project_dirs,
@@ -85,19 +140,31 @@ impl AddProject {
             if let Some(description) = description {
                 value.description = Some(description);
             }
+            if icon.is_some() {
+                value.icon = icon;
+            }
+            if editor.is_some() {
+                value.editor = editor;
+            }
+            if open_workspace.is_some() {
+                value.open_workspace = open_workspace;
+            }
             value.clone()
         } else {
             let new_project = Project {
                 path,
                 name,
-                icon: None,
+                icon,
                 description,
+                editor,
+                workspace: None,
+                open_workspace,
             };
 
             config.projects.push(new_project.clone());
             new_project
         };
-        project_dirs.write_config(&config)?;
+        project_dirs.write_config(config)?;
         Ok(project)
     }
 }
@@ -107,20 +174,56 @@ pub struct AddProjectsDir {
     name: Option<String>,
     #[arg(short, long)]
     icon_style: Option<IconStyle>,
+    /// Name of a template in the templates directory next to config.toml. Replaces --icon-style
+    #[arg(long)]
+    icon_template: Option<String>,
     #[arg(short, long)]
     description: Option<String>,
+    /// The id of the editor projects in the location are opened in
+    #[arg(long)]
+    editor: Option<String>,
+    /// Open the directories even if projects have a .code-workspace file
+    #[arg(long)]
+    no_workspace: bool,
     /// If not provided, the current directory will be used
     #[arg(short, long)]
     path: Option<PathBuf>,
+    /// Directories less than this deep are never projects. Defaults to 1
+    #[arg(long)]
+    min_depth: Option<usize>,
+    /// How deep to look for projects. Defaults to 1
+    #[arg(long)]
+    max_depth: Option<usize>,
+    /// A file name or glob pattern that marks a directory as a project.
+    ///
+    /// Can be used multiple times. Defaults to common project files such as .git and Cargo.toml
+    #[arg(long = "marker")]
+    markers: Vec<String>,
+    /// Only directories matching this glob pattern are projects. Can be used multiple times
+    #[arg(long)]
+    include: Vec<String>,
+    /// Skip directories matching this glob pattern. Can be used multiple times
+    #[arg(long)]
+    exclude: Vec<String>,
 }
 impl AddProjectsDir {
     pub fn execute(self, app_state: AppState) -> anyhow::Result<()> {
         let Self {
             name,
             icon_style,
+            icon_template,
             description,
+            editor,
+            no_workspace,
             path,
+            min_depth,
+            max_depth,
+            markers,
+            include,
+            exclude,
         } = self;
+        let markers = Some(markers).filter(|markers| !markers.is_empty());
+        let open_workspace = no_workspace.then_some(false);
 
         let path = if let Some(path) = path {
             path
@@ -137,6 +240,12 @@ impl AddProjectsDir {
             info!("Using {} as the name", name);
             name
         };
+        if let Some(icon_template) = &icon_template {
+            icon::validate_template(icon_template, &app_state)?;
+        }
+        if let Some(editor) = &editor {
+            app_state.config.validate_editor(editor)?;
+        }
         let AppState {
             mut config,
             project_dirs,
@@ -148,19 +257,112 @@ impl AddProjectsDir {
             if let Some(icon_style) = icon_style {
                 value.icon_style = icon_style;
             }
+            if icon_template.is_some() {
+                value.icon_template = icon_template;
+            }
             if let Some(description) = description {
                 value.description = Some(description);
             }
+            if editor.is_some() {
+                value.editor = editor;
+            }
+            if open_workspace.is_some() {
+                value.open_workspace = open_workspace;
+            }
+            if let Some(min_depth) = min_depth {
+                value.min_depth = min_depth;
+            }
+            if let Some(max_depth) = max_depth {
+                value.max_depth = max_depth;
+            }
+            if markers.is_some() {
+                value.markers = markers;
+            }
+            value.include.extend(include);
+            value.exclude.extend(exclude);
+            value.validate()?;
         } else {
+            let min_depth = min_depth.unwrap_or(1);
             let new_project = ProjectLocation {
                 path,
                 name: Some(name),
                 icon_style: icon_style.unwrap_or_default(),
+                icon_template,
+                editor,
+                open_workspace,
                 description,
+                min_depth,
+                max_depth: max_depth.unwrap_or(min_depth),
+                markers,
+                include,
+                exclude,
             };
+            new_project.validate()?;
             config.project_locations.push(new_project);
         }
         project_dirs.write_config(&config)?;
         Ok(())
     }
 }
+#[derive(Args, Debug)]
+pub struct DetectEditors {
+    /// Makes the editor with this id the default and saves where it was found
+    #[arg(long = "use")]
+    use_editor: Option<String>,
+}
+impl DetectEditors {
+    pub fn execute(self, app_state: AppState) -> anyhow::Result<()> {
+        let base_dirs =
+            directories::BaseDirs::new().context("Unable to Locate User Directories?")?;
+        let detected = editor::detect_editors(&base_dirs);
+        let Some(id) = self.use_editor else {
+            print_detected(&detected);
+            return Ok(());
+        };
+        let DetectedEditor { editor, source } = detected
+            .into_iter()
+            .find(|detected| detected.editor.id == id)
+            .ok_or_else(|| anyhow::anyhow!("{} was not found", id))?;
+        let AppState {
+            mut config,
+            project_dirs,
+        } = app_state;
+        info!(
+            "Using {} from {} ({})",
+            editor.name,
+            editor.executable.display(),
+            source
+        );
+        if id == editor::VS_CODE {
+            config.vs_code_path = editor.executable.clone();
+        }
+        // A configured editor replaces the preset, so it is updated too
+        if let Some(configured) = config.editors.iter_mut().find(|e| e.id == id) {
+            configured.executable = editor.executable;
+        } else if id != editor::VS_CODE {
+            config.editors.push(editor);
+        }
+        config.editor = id;
+        project_dirs.write_config(&config)?;
+        Ok(())
+    }
+}
+/// Prints the editors found by [editor::detect_editors]
+pub fn print_detected(detected: &[DetectedEditor]) {
+    if detected.is_empty() {
+        println!("No editors found");
+        return;
+    }
+    let rows = detected
+        .iter()
+        .map(|detected| {
+            vec![
+                detected.editor.id.clone(),
+                detected.editor.name.clone(),
+                detected.editor.executable.display().to_string(),
+                detected.source.to_string(),
+            ]
+        })
+        .collect();
+    print_table(&["Id", "Name", "Executable", "Source"], rows);
+}
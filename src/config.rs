@@ -1,51 +1,173 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use clap::ValueEnum;
 use log::debug;
 use serde::{Deserialize, Serialize};
 use strum::AsRefStr;
+
+use crate::{
+    discovery::{MarkerSet, PathFilter},
+    editor::{self, Editor, EditorRule, EditorRules},
+    shortcuts::ShortcutBackendConfig,
+};
 #[cfg(target_os = "windows")]
-fn default_vs_code_path() -> PathBuf {
-    which::which("code").unwrap_or_else(|e| {
-        debug!("Unable to find VS Code: {}", e);
-        PathBuf::from("C:\\Program Files\\Microsoft VS Code\\Code.exe")
-    })
-}
+const FALLBACK_VS_CODE_PATH: &str = "C:\\Program Files\\Microsoft VS Code\\Code.exe";
 #[cfg(not(target_os = "windows"))]
+const FALLBACK_VS_CODE_PATH: &str = "/usr/bin/code";
+/// VS Code on the `PATH`. `detect-editors` finds other installations
 fn default_vs_code_path() -> PathBuf {
     which::which("code").unwrap_or_else(|e| {
         debug!("Unable to find VS Code: {}", e);
-        PathBuf::from("/usr/bin/code")
+        PathBuf::from(FALLBACK_VS_CODE_PATH)
     })
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
+    /// The executable of the `code` editor
+    #[serde(default = "default_vs_code_path")]
     pub vs_code_path: PathBuf,
+    /// The id of the editor projects are opened in
+    #[serde(default = "default_editor")]
+    pub editor: String,
+    /// Editors besides the presets. An editor replaces the preset with the same id
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub editors: Vec<Editor>,
+    /// Picks the editor of projects without one. The first matching rule is used
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub editor_rules: Vec<EditorRule>,
     #[serde(default)]
     pub project_locations: Vec<ProjectLocation>,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub disabled_projects: Vec<PathBuf>,
+    /// Glob patterns excluded from every project location
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub projects: Vec<Project>,
+    /// Where the shortcuts are created
+    #[serde(default)]
+    pub shortcut_backend: ShortcutBackendConfig,
+    /// The sizes rendered into every generated icon. At most 256
+    #[serde(default = "default_icon_sizes")]
+    pub icon_sizes: Vec<u32>,
+    /// Also install icons as PNG and SVG in the hicolor icon theme so desktop entries can use them.
+    ///
+    /// Defaults to true on Linux
+    #[serde(default = "default_icon_theme")]
+    pub icon_theme: bool,
+    /// Also write an Apple `.icns` file next to every generated icon
+    #[serde(default)]
+    pub icns: bool,
+    /// Render glyphs missing from the bundled fonts with the fonts installed on the system.
+    ///
+    /// Off by default because loading them is slow and icons then depend on the machine
+    #[serde(default)]
+    pub system_fonts: bool,
+}
+fn default_editor() -> String {
+    editor::VS_CODE.to_string()
+}
+fn default_icon_sizes() -> Vec<u32> {
+    vec![16, 24, 32, 48, 64, 128, 256]
+}
+fn default_icon_theme() -> bool {
+    cfg!(target_os = "linux")
 }
 impl Default for Config {
     fn default() -> Self {
         let code = default_vs_code_path();
         Self {
             vs_code_path: code,
+            editor: default_editor(),
+            editors: vec![],
+            editor_rules: vec![],
             project_locations: vec![],
             disabled_projects: vec![],
+            exclude: vec![],
             projects: Vec::new(),
+            shortcut_backend: Default::default(),
+            icon_sizes: default_icon_sizes(),
+            icon_theme: default_icon_theme(),
+            icns: false,
+            system_fonts: false,
         }
     }
 }
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, ValueEnum, AsRefStr)]
+impl Config {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let editors = std::iter::once(&self.editor)
+            .chain(self.editor_rules.iter().map(|rule| &rule.editor))
+            .chain(self.projects.iter().filter_map(|p| p.editor.as_ref()))
+            .chain(
+                self.project_locations
+                    .iter()
+                    .filter_map(|l| l.editor.as_ref()),
+            );
+        for editor in editors {
+            self.validate_editor(editor)?;
+        }
+        EditorRules::new(&self.editor_rules)?;
+        Ok(())
+    }
+    /// Checks that an editor with the id exists
+    pub fn validate_editor(&self, id: &str) -> anyhow::Result<()> {
+        match self.find_editor(id) {
+            Some(_) => Ok(()),
+            None => Err(anyhow::anyhow!("No editor with the id {}", id)),
+        }
+    }
+    /// Finds a configured editor or a preset
+    pub fn find_editor(&self, id: &str) -> Option<Editor> {
+        let preset = editor::presets().into_iter().find(|editor| editor.id == id);
+        if let Some(editor) = self.editors.iter().find(|editor| editor.id == id) {
+            let mut editor = editor.clone();
+            if editor.workspaces.is_none() {
+                editor.workspaces = preset.and_then(|preset| preset.workspaces);
+            }
+            return Some(editor);
+        }
+        let mut preset = preset?;
+        if preset.id == editor::VS_CODE {
+            preset.executable = self.vs_code_path.clone();
+        }
+        Some(preset)
+    }
+    /// The editor projects are opened in. [Config::validate] checks that it exists
+    pub fn default_editor(&self) -> Editor {
+        self.find_editor(&self.editor)
+            .expect("The editor is checked by Config::validate")
+    }
+    /// The editor of the project or [Config::default_editor]
+    pub fn editor_for(&self, project: &Project) -> Editor {
+        match &project.editor {
+            Some(id) => self
+                .find_editor(id)
+                .expect("Project editors are checked by Config::validate"),
+            None => self.default_editor(),
+        }
+    }
+}
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default, ValueEnum, AsRefStr,
+)]
 pub enum IconStyle {
     /// Use VS Code's Default Icon
     #[default]
     Default,
+    /// A Rust themed gear
+    #[value(alias = "rust")]
+    #[serde(alias = "Rust")]
     Cargo,
+    TypeScript,
+    JavaScript,
+    Python,
+    Go,
+    Java,
+    /// Two initials on a color picked from the project name
+    Initials,
+    /// Picks the style from the files in the project directory
+    Auto,
 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProjectLocation {
@@ -53,7 +175,93 @@ pub struct ProjectLocation {
     pub name: Option<String>,
     #[serde(default)]
     pub icon_style: IconStyle,
+    /// Name of a template in the `templates` directory next to `config.toml`. Replaces `icon_style`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon_template: Option<String>,
+    /// The id of the editor projects in this location are opened in. Replaces `editor_rules`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub editor: Option<String>,
+    /// Open the `.code-workspace` file of a project instead of its directory. Defaults to true
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub open_workspace: Option<bool>,
     pub description: Option<String>,
+    /// Directories less than this deep are never projects. The children of `path` are at depth 1
+    #[serde(default = "default_depth")]
+    pub min_depth: usize,
+    /// How deep to look for projects
+    #[serde(default = "default_depth")]
+    pub max_depth: usize,
+    /// File names or glob patterns. A directory containing a match is a project.
+    ///
+    /// Defaults to [DEFAULT_MARKERS]. Use `["*"]` to make every non-empty directory a project
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub markers: Option<Vec<String>>,
+    /// Glob patterns. If set only matching directories are projects
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+    /// Glob patterns. Matching directories are skipped and not searched
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+}
+/// Files that are found in the root of most projects
+pub const DEFAULT_MARKERS: &[&str] = &[
+    ".git",
+    ".hg",
+    ".svn",
+    "Cargo.toml",
+    "package.json",
+    "deno.json",
+    "pyproject.toml",
+    "setup.py",
+    "requirements.txt",
+    "go.mod",
+    "pom.xml",
+    "build.gradle",
+    "build.gradle.kts",
+    "*.sln",
+    "*.csproj",
+    "CMakeLists.txt",
+    "Makefile",
+    "composer.json",
+    "Gemfile",
+    "mix.exs",
+    "*.code-workspace",
+];
+fn default_depth() -> usize {
+    1
+}
+impl ProjectLocation {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.min_depth == 0 {
+            return Err(anyhow::anyhow!(
+                "min_depth of {} must be at least 1",
+                self.path.display()
+            ));
+        }
+        if self.max_depth < self.min_depth {
+            return Err(anyhow::anyhow!(
+                "max_depth of {} must not be less than min_depth",
+                self.path.display()
+            ));
+        }
+        MarkerSet::new(self.markers())?;
+        PathFilter::new(self, &[])?;
+        Ok(())
+    }
+    /// The markers of the location or [DEFAULT_MARKERS]
+    pub fn markers(&self) -> Vec<&str> {
+        match &self.markers {
+            Some(markers) => markers.iter().map(String::as_str).collect(),
+            None => DEFAULT_MARKERS.to_vec(),
+        }
+    }
+    /// The template used for a project in this location
+    pub fn icon_template(&self, project: &Path) -> String {
+        match &self.icon_template {
+            Some(template) => template.clone(),
+            None => self.icon_style.resolve(project).template().to_string(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -62,4 +270,17 @@ pub struct Project {
     pub name: String,
     pub icon: Option<PathBuf>,
     pub description: Option<String>,
+    /// The id of the editor the project is opened in.
+    ///
+    /// Replaces the editor of the location and `editor_rules`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub editor: Option<String>,
+    /// The `.code-workspace` file opened instead of [Project::path].
+    ///
+    /// Found during discovery if not set. Only used by editors that support workspaces
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workspace: Option<PathBuf>,
+    /// Open [Project::workspace] instead of the directory. Defaults to true
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub open_workspace: Option<bool>,
 }
@@ -0,0 +1,657 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use log::{debug, warn};
+use rayon::prelude::*;
+use serde::Serialize;
+
+use crate::{
+    config::{Project, ProjectLocation},
+    editor::EditorRules,
+    icon::{self, IconRequest},
+    report::{Failure, Stage},
+    AppState,
+};
+
+/// A project that will get a shortcut
+#[derive(Debug, Clone, Serialize)]
+pub struct DiscoveredProject {
+    pub project: Project,
+    /// The [ProjectLocation](crate::config::ProjectLocation) the project was found in.
+    ///
+    /// None for projects added with `add-project`
+    pub location: Option<PathBuf>,
+    /// The icon that has to be rendered to [Project::icon]
+    pub render_icon: Option<IconRequest>,
+}
+
+/// The result of [discover_projects]
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Discovery {
+    pub projects: Vec<DiscoveredProject>,
+    /// Projects that were skipped because they are in `disabled_projects`
+    pub disabled: Vec<PathBuf>,
+    /// Entries of `disabled_projects` that did not match any project
+    pub unmatched_disabled: Vec<PathBuf>,
+    /// Locations or directories that could not be searched
+    pub failures: Vec<Failure>,
+}
+/// Finds all projects without rendering any icons.
+///
+/// Directories that can not be read are recorded in [Discovery::failures]
+pub fn discover_projects(state: &AppState) -> Discovery {
+    let mut disabled = DisabledProjects::new(&state.config.disabled_projects);
+    let mut discovery = Discovery::default();
+    let mut add_project = |discovered: DiscoveredProject| {
+        if disabled.is_disabled(&discovered.project.path) {
+            debug!("Skipping disabled project {}", discovered.project.name);
+            discovery.disabled.push(discovered.project.path);
+        } else {
+            discovery.projects.push(discovered);
+        }
+    };
+    let editor_rules = EditorRules::new(&state.config.editor_rules).unwrap_or_else(|error| {
+        discovery
+            .failures
+            .push(Failure::new("editor_rules", Stage::Discovery, error));
+        EditorRules::default()
+    });
+    let select_editor = |path: &Path| editor_rules.select(path).map(String::from);
+    for project in &state.config.projects {
+        add_project(DiscoveredProject {
+            project: discover_added_project(project, &editor_rules),
+            location: None,
+            render_icon: None,
+        });
+    }
+    // Locations are searched in parallel, projects are added in the configured order
+    let searched: Vec<_> = state
+        .config
+        .project_locations
+        .par_iter()
+        .map(|project_location| {
+            let mut failures = Vec::new();
+            let paths = project_location.validate().and_then(|_| {
+                find_project_directories(project_location, &state.config.exclude, &mut failures)
+            });
+            (project_location, paths, failures)
+        })
+        .collect();
+    for (project_location, paths, failures) in searched {
+        discovery.failures.extend(failures);
+        let paths = match paths {
+            Ok(paths) => paths,
+            Err(error) => {
+                discovery.failures.push(Failure::new(
+                    project_location.path.display().to_string(),
+                    Stage::Discovery,
+                    error,
+                ));
+                continue;
+            }
+        };
+        for path in paths {
+            let directory_name = path.file_name().unwrap().to_string_lossy().to_string();
+            let location_name = project_location.name.as_deref().unwrap_or("Project");
+            let name = format!(
+                "{} - {}",
+                project_name(&project_location.path, &path),
+                location_name
+            );
+            let render_icon = IconRequest {
+                template: project_location.icon_template(&path),
+                name: directory_name,
+                location: Some(location_name.to_string()),
+            };
+            let editor = project_location
+                .editor
+                .clone()
+                .or_else(|| select_editor(&path));
+            let workspace = project_location
+                .open_workspace
+                .unwrap_or(true)
+                .then(|| find_workspace(&path))
+                .flatten();
+            let project = Project {
+                path,
+                icon: Some(icon::icon_path(&name, state)),
+                name,
+                editor,
+                workspace,
+                ..Default::default()
+            };
+            add_project(DiscoveredProject {
+                render_icon: Some(render_icon),
+                project,
+                location: Some(project_location.path.clone()),
+            });
+        }
+    }
+    discovery.unmatched_disabled = disabled.unmatched();
+    discovery
+}
+/// The name of a project directory in a location.
+///
+/// Projects deeper than the children of the location get their parents appended. `lib (org-b)` for `org-b/lib`
+fn project_name(location: &Path, project: &Path) -> String {
+    let name = project.file_name().unwrap().to_string_lossy().to_string();
+    let parents: Vec<_> = project
+        .strip_prefix(location)
+        .ok()
+        .and_then(Path::parent)
+        .map(|parents| {
+            parents
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect()
+        })
+        .unwrap_or_default();
+    if parents.is_empty() {
+        name
+    } else {
+        format!("{} ({})", name, parents.join(", "))
+    }
+}
+/// Picks the editor and the workspace of a project added with `add-project` if they are not set.
+///
+/// A workspace that is set is resolved against the project directory and dropped if it does not exist
+pub fn discover_added_project(project: &Project, editor_rules: &EditorRules) -> Project {
+    let mut project = project.clone();
+    if project.editor.is_none() {
+        project.editor = editor_rules.select(&project.path).map(String::from);
+    }
+    if project.open_workspace == Some(false) {
+        project.workspace = None;
+    } else if let Some(workspace) = &project.workspace {
+        // Relative workspaces are relative to the project
+        let workspace = project.path.join(workspace);
+        if workspace.is_file() {
+            project.workspace = Some(workspace);
+        } else {
+            warn!(
+                "Workspace {} of {} does not exist. Opening the directory instead",
+                workspace.display(),
+                project.name
+            );
+            project.workspace = None;
+        }
+    } else {
+        project.workspace = find_workspace(&project.path);
+    }
+    project
+}
+/// The `.code-workspace` file in the root of a project.
+///
+/// Prefers `<directory name>.code-workspace`, otherwise the first in alphabetical order
+pub fn find_workspace(project: &Path) -> Option<PathBuf> {
+    if let Some(name) = project.file_name() {
+        let mut file_name = name.to_os_string();
+        file_name.push(".code-workspace");
+        let preferred = project.join(file_name);
+        if preferred.is_file() {
+            return Some(preferred);
+        }
+    }
+    let mut workspaces: Vec<PathBuf> = project
+        .read_dir()
+        .ok()?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "code-workspace")
+                && path.is_file()
+        })
+        .collect();
+    workspaces.sort();
+    workspaces.into_iter().next()
+}
+/// Checks paths against `disabled_projects`.
+///
+/// A project is disabled if it or any of its parents are disabled
+#[derive(Debug)]
+pub struct DisabledProjects {
+    /// The configured path, the normalized path and if it matched a project
+    entries: Vec<(PathBuf, PathBuf, bool)>,
+}
+impl DisabledProjects {
+    pub fn new(disabled: &[PathBuf]) -> Self {
+        Self {
+            entries: disabled
+                .iter()
+                .map(|path| (path.clone(), normalize_path(path), false))
+                .collect(),
+        }
+    }
+
+    pub fn is_disabled(&mut self, path: &Path) -> bool {
+        let path = normalize_path(path);
+        let mut disabled = false;
+        for (_, normalized, matched) in &mut self.entries {
+            if path.starts_with(normalized) {
+                *matched = true;
+                disabled = true;
+            }
+        }
+        disabled
+    }
+    /// Entries that have not matched any path yet
+    pub fn unmatched(&self) -> Vec<PathBuf> {
+        self.entries
+            .iter()
+            .filter(|(_, _, matched)| !matched)
+            .map(|(path, _, _)| path.clone())
+            .collect()
+    }
+}
+/// Resolves symlinks and removes trailing slashes. Paths are lowercase on Windows
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let path = path
+        .canonicalize()
+        .unwrap_or_else(|_| path.components().collect());
+    if cfg!(target_os = "windows") {
+        PathBuf::from(path.to_string_lossy().to_lowercase())
+    } else {
+        path
+    }
+}
+
+/// Walks the location looking for projects.
+///
+/// Once a directory is a project its children are not searched.
+/// Directories that can not be read are added to `failures` and skipped
+pub fn find_project_directories(
+    location: &ProjectLocation,
+    global_exclude: &[String],
+    failures: &mut Vec<Failure>,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let walker = LocationWalker {
+        location,
+        markers: MarkerSet::new(location.markers())?,
+        filter: PathFilter::new(location, global_exclude)?,
+    };
+    let mut projects = Vec::new();
+    walker.walk(&location.path, 1, &mut projects, failures);
+    Ok(projects)
+}
+struct LocationWalker<'a> {
+    location: &'a ProjectLocation,
+    markers: MarkerSet,
+    filter: PathFilter,
+}
+impl LocationWalker<'_> {
+    fn walk(
+        &self,
+        directory: &Path,
+        depth: usize,
+        projects: &mut Vec<PathBuf>,
+        failures: &mut Vec<Failure>,
+    ) {
+        let failure =
+            |error| Failure::new(directory.display().to_string(), Stage::Discovery, error);
+        let entries = match directory
+            .read_dir()
+            .context(format!("Unable to read directory {}", directory.display()))
+        {
+            Ok(entries) => entries,
+            Err(error) => {
+                failures.push(failure(error));
+                return;
+            }
+        };
+        let mut children = Vec::with_capacity(entries.size_hint().0);
+        for entry in entries {
+            let entry = match entry.context("Unable to Read Project Folder") {
+                Ok(entry) => entry,
+                Err(error) => {
+                    failures.push(failure(error));
+                    continue;
+                }
+            };
+            let path = entry.path();
+            if path.is_dir() && entry.file_name() != ".git" {
+                children.push(path);
+            }
+        }
+        children.sort();
+        // Subdirectories are searched in parallel. Collecting keeps the sorted order
+        let found: Vec<(Vec<PathBuf>, Vec<Failure>)> = children
+            .into_par_iter()
+            .map(|path| {
+                let mut projects = Vec::new();
+                let mut failures = Vec::new();
+                self.visit(path, depth, &mut projects, &mut failures);
+                (projects, failures)
+            })
+            .collect();
+        for (child_projects, child_failures) in found {
+            projects.extend(child_projects);
+            failures.extend(child_failures);
+        }
+    }
+    /// Adds `path` as a project or searches it
+    fn visit(
+        &self,
+        path: PathBuf,
+        depth: usize,
+        projects: &mut Vec<PathBuf>,
+        failures: &mut Vec<Failure>,
+    ) {
+        let relative = path.strip_prefix(&self.location.path).unwrap_or(&path);
+        if self.filter.is_excluded(relative) {
+            debug!("Excluded {}", path.display());
+            return;
+        }
+        if depth >= self.location.min_depth
+            && self.filter.is_included(relative)
+            && self.markers.matches(&path)
+        {
+            projects.push(path);
+        } else if depth < self.location.max_depth {
+            self.walk(&path, depth + 1, projects, failures);
+        }
+    }
+}
+/// The include and exclude patterns of a location.
+///
+/// Patterns are matched against the directory name and the path relative to the location
+#[derive(Debug, Clone)]
+pub struct PathFilter {
+    /// If empty every directory is included
+    include: GlobSet,
+    exclude: GlobSet,
+}
+impl PathFilter {
+    pub fn new(location: &ProjectLocation, global_exclude: &[String]) -> anyhow::Result<Self> {
+        Ok(Self {
+            include: build_glob_set(&location.include)?,
+            exclude: build_glob_set(location.exclude.iter().chain(global_exclude))?,
+        })
+    }
+    /// Excluded directories are not projects and are not searched
+    pub fn is_excluded(&self, relative: &Path) -> bool {
+        Self::matches(&self.exclude, relative)
+    }
+    /// Directories that are not included can still be searched for projects
+    pub fn is_included(&self, relative: &Path) -> bool {
+        self.include.is_empty() || Self::matches(&self.include, relative)
+    }
+
+    fn matches(set: &GlobSet, relative: &Path) -> bool {
+        relative.file_name().is_some_and(|name| set.is_match(name)) || set.is_match(relative)
+    }
+}
+fn build_glob_set<'a>(patterns: impl IntoIterator<Item = &'a String>) -> anyhow::Result<GlobSet> {
+    let mut set = GlobSetBuilder::new();
+    for pattern in patterns {
+        set.add(Glob::new(pattern).context(format!("Invalid glob pattern {}", pattern))?);
+    }
+    Ok(set.build()?)
+}
+/// Files that mark a directory as a project. Such as `Cargo.toml` or `*.sln`
+#[derive(Debug, Clone)]
+pub struct MarkerSet {
+    /// Markers without any glob characters. Checked without reading the directory
+    literals: Vec<String>,
+    patterns: GlobSet,
+}
+impl MarkerSet {
+    pub fn new<'a>(markers: impl IntoIterator<Item = &'a str>) -> anyhow::Result<Self> {
+        let mut literals = Vec::new();
+        let mut patterns = GlobSetBuilder::new();
+        for marker in markers {
+            if marker.contains(['*', '?', '[', '{']) {
+                patterns
+                    .add(Glob::new(marker).context(format!("Invalid project marker {}", marker))?);
+            } else {
+                literals.push(marker.to_string());
+            }
+        }
+        Ok(Self {
+            literals,
+            patterns: patterns.build()?,
+        })
+    }
+    /// Checks if the directory contains any of the markers
+    pub fn matches(&self, directory: &Path) -> bool {
+        if self
+            .literals
+            .iter()
+            .any(|marker| directory.join(marker).exists())
+        {
+            return true;
+        }
+        if self.patterns.is_empty() {
+            return false;
+        }
+        let Ok(entries) = directory.read_dir() else {
+            return false;
+        };
+        entries
+            .filter_map(Result::ok)
+            .any(|entry| self.patterns.is_match(entry.file_name()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use crate::config::ProjectLocation;
+
+    fn location(path: &Path) -> ProjectLocation {
+        ProjectLocation {
+            path: path.to_path_buf(),
+            name: Some("Code".to_string()),
+            icon_style: Default::default(),
+            icon_template: None,
+            editor: None,
+            open_workspace: None,
+            description: None,
+            min_depth: 1,
+            max_depth: 1,
+            markers: None,
+            include: vec![],
+            exclude: vec![],
+        }
+    }
+    fn create_dirs(root: &Path, dirs: &[&str]) {
+        for dir in dirs {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+    }
+    fn found(location: &ProjectLocation) -> Vec<String> {
+        found_with_global(location, &[])
+    }
+    fn found_with_global(location: &ProjectLocation, global_exclude: &[String]) -> Vec<String> {
+        let mut failures = Vec::new();
+        let found =
+            super::find_project_directories(location, global_exclude, &mut failures).unwrap();
+        assert!(failures.is_empty());
+        found
+            .into_iter()
+            .map(|path| {
+                path.strip_prefix(&location.path)
+                    .unwrap()
+                    .to_string_lossy()
+                    .replace('\\', "/")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn depth() {
+        let root = tempfile::tempdir().unwrap();
+        create_dirs(
+            root.path(),
+            &[
+                "org/repo/.git",
+                "org/repo/nested/.git",
+                "org/repo/src",
+                "org/other/src",
+                "repo/.git",
+                "deep/a/b/.git",
+            ],
+        );
+        let mut location = location(root.path());
+        assert_eq!(found(&location), vec!["repo"]);
+
+        location.min_depth = 2;
+        location.max_depth = 2;
+        assert_eq!(found(&location), vec!["org/repo"]);
+
+        location.min_depth = 1;
+        location.max_depth = 3;
+        assert_eq!(found(&location), vec!["deep/a/b", "org/repo", "repo"]);
+    }
+    #[test]
+    fn markers() {
+        let root = tempfile::tempdir().unwrap();
+        create_dirs(
+            root.path(),
+            &[
+                "rust/src",
+                "dotnet",
+                "node/node_modules",
+                "target/debug",
+                "empty",
+            ],
+        );
+        std::fs::write(root.path().join("rust/Cargo.toml"), "").unwrap();
+        std::fs::write(root.path().join("dotnet/App.sln"), "").unwrap();
+        std::fs::write(root.path().join("node/package.json"), "").unwrap();
+        let mut location = location(root.path());
+        assert_eq!(found(&location), vec!["dotnet", "node", "rust"]);
+
+        location.markers = Some(vec!["*.sln".to_string()]);
+        assert_eq!(found(&location), vec!["dotnet"]);
+
+        location.markers = Some(vec!["*".to_string()]);
+        assert_eq!(found(&location), vec!["dotnet", "node", "rust", "target"]);
+
+        location.markers = Some(vec!["[".to_string()]);
+        assert!(super::find_project_directories(&location, &[], &mut Vec::new()).is_err());
+    }
+    #[test]
+    fn disabled_projects() {
+        let root = tempfile::tempdir().unwrap();
+        create_dirs(root.path(), &["a", "b", "org/c", "org/d"]);
+        let mut disabled = super::DisabledProjects::new(&[
+            PathBuf::from(format!("{}/", root.path().join("a").display())),
+            root.path().join("org"),
+            root.path().join("removed"),
+        ]);
+        assert!(disabled.is_disabled(&root.path().join("a")));
+        assert!(!disabled.is_disabled(&root.path().join("b")));
+        assert!(disabled.is_disabled(&root.path().join("org").join("c")));
+        assert!(!disabled.is_disabled(&root.path().join("org2")));
+        assert_eq!(disabled.unmatched(), vec![root.path().join("removed")]);
+    }
+    #[test]
+    #[cfg(unix)]
+    fn disabled_symlink() {
+        let root = tempfile::tempdir().unwrap();
+        create_dirs(root.path(), &["project"]);
+        let link = root.path().join("link");
+        std::os::unix::fs::symlink(root.path().join("project"), &link).unwrap();
+
+        let mut disabled = super::DisabledProjects::new(&[link]);
+        assert!(disabled.is_disabled(&root.path().join("project")));
+        assert!(disabled.unmatched().is_empty());
+    }
+    #[test]
+    fn include_exclude() {
+        let root = tempfile::tempdir().unwrap();
+        create_dirs(
+            root.path(),
+            &[
+                "app/.git",
+                "app.bak/.git",
+                "archive-2020/.git",
+                ".hidden/.git",
+                "org/lib/.git",
+                "org/tool/.git",
+                "other/lib/.git",
+            ],
+        );
+        let mut location = location(root.path());
+        location.max_depth = 2;
+        assert_eq!(
+            found(&location),
+            vec![
+                ".hidden",
+                "app",
+                "app.bak",
+                "archive-2020",
+                "org/lib",
+                "org/tool",
+                "other/lib"
+            ]
+        );
+        location.exclude = vec!["archive-*".to_string(), "*.bak".to_string()];
+        assert_eq!(
+            found_with_global(&location, &[".*".to_string()]),
+            vec!["app", "org/lib", "org/tool", "other/lib"]
+        );
+        // Matches against the relative path
+        location.exclude = vec!["org/tool".to_string()];
+        location.include = vec!["lib".to_string(), "app*".to_string()];
+        assert_eq!(
+            found_with_global(&location, &[".*".to_string()]),
+            vec!["app", "app.bak", "org/lib", "other/lib"]
+        );
+    }
+    #[test]
+    fn workspaces() {
+        let root = tempfile::tempdir().unwrap();
+        let project = root.path().join("app");
+        create_dirs(&project, &["dir.code-workspace"]);
+        let workspace = |name: &str| {
+            std::fs::write(project.join(name), "{}").unwrap();
+            project.join(name)
+        };
+        assert_eq!(super::find_workspace(&project), None);
+        let b = workspace("b.code-workspace");
+        workspace("c.code-workspace");
+        assert_eq!(super::find_workspace(&project), Some(b.clone()));
+        let a = workspace("a.code-workspace");
+        assert_eq!(super::find_workspace(&project), Some(a));
+        let preferred = workspace("app.code-workspace");
+        assert_eq!(super::find_workspace(&project), Some(preferred.clone()));
+
+        let rules = crate::editor::EditorRules::default();
+        let mut added = crate::config::Project {
+            path: project.clone(),
+            name: "app".to_string(),
+            ..Default::default()
+        };
+        let discovered = super::discover_added_project(&added, &rules);
+        assert_eq!(discovered.workspace, Some(preferred));
+        added.workspace = Some(b.clone());
+        let discovered = super::discover_added_project(&added, &rules);
+        assert_eq!(discovered.workspace, Some(b.clone()));
+        added.workspace = Some(PathBuf::from("b.code-workspace"));
+        let discovered = super::discover_added_project(&added, &rules);
+        assert_eq!(discovered.workspace, Some(b));
+        added.workspace = Some(PathBuf::from("missing.code-workspace"));
+        let discovered = super::discover_added_project(&added, &rules);
+        assert_eq!(discovered.workspace, None);
+        added.open_workspace = Some(false);
+        let discovered = super::discover_added_project(&added, &rules);
+        assert_eq!(discovered.workspace, None);
+    }
+    #[test]
+    fn project_names() {
+        let location = Path::new("/code");
+        assert_eq!(super::project_name(location, Path::new("/code/lib")), "lib");
+        assert_eq!(
+            super::project_name(location, Path::new("/code/org-a/lib")),
+            "lib (org-a)"
+        );
+        assert_eq!(
+            super::project_name(location, Path::new("/code/org-b/team/lib")),
+            "lib (org-b, team)"
+        );
+    }
+}
@@ -0,0 +1,195 @@
+use std::path::PathBuf;
+
+use clap::ValueEnum;
+use console::{measure_text_width, pad_str, style, Alignment};
+use serde::Serialize;
+
+use crate::{
+    discovery::Discovery,
+    shortcuts::{InstalledShortcut, Shortcut, ShortcutBackend, SyncPlan, SyncSummary},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum OutputFormat {
+    /// Human readable tables
+    #[default]
+    Table,
+    Json,
+}
+
+/// Everything `build-shortcuts` would do
+#[derive(Debug, Serialize)]
+pub struct DryRun {
+    #[serde(flatten)]
+    pub discovery: Discovery,
+    pub shortcuts: PlannedShortcuts,
+    pub summary: SyncSummary,
+}
+#[derive(Debug, Default, Serialize)]
+pub struct PlannedShortcuts {
+    pub create: Vec<PlannedShortcut>,
+    pub update: Vec<PlannedShortcut>,
+    pub remove: Vec<PlannedShortcut>,
+    pub unchanged: Vec<PlannedShortcut>,
+    /// Not removed because discovery failed
+    pub keep: Vec<PlannedShortcut>,
+}
+#[derive(Debug, Serialize)]
+pub struct PlannedShortcut {
+    pub name: String,
+    pub path: PathBuf,
+}
+impl DryRun {
+    pub fn new(discovery: Discovery, plan: &SyncPlan, backend: &dyn ShortcutBackend) -> Self {
+        let planned = |shortcuts: &[Shortcut]| {
+            shortcuts
+                .iter()
+                .map(|shortcut| PlannedShortcut {
+                    name: shortcut.name.clone(),
+                    path: backend.shortcut_path(shortcut),
+                })
+                .collect()
+        };
+        let installed = |shortcuts: &[InstalledShortcut]| {
+            shortcuts
+                .iter()
+                .map(|installed| PlannedShortcut {
+                    name: installed.name.clone(),
+                    path: installed.path.clone(),
+                })
+                .collect()
+        };
+        let shortcuts = PlannedShortcuts {
+            create: planned(&plan.create),
+            update: planned(&plan.update),
+            remove: installed(&plan.remove),
+            unchanged: planned(&plan.unchanged),
+            keep: installed(&plan.kept),
+        };
+        Self {
+            discovery,
+            shortcuts,
+            summary: plan.summary(),
+        }
+    }
+
+    pub fn print(&self, format: OutputFormat) -> anyhow::Result<()> {
+        match format {
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(self)?),
+            OutputFormat::Table => self.print_tables(),
+        }
+        Ok(())
+    }
+
+    fn print_tables(&self) {
+        println!("{}", style("Projects").bold());
+        let rows = self
+            .discovery
+            .projects
+            .iter()
+            .map(|discovered| {
+                let location = discovered
+                    .location
+                    .as_ref()
+                    .map(|location| location.display().to_string())
+                    .unwrap_or_else(|| "(added project)".to_string());
+                let icon = match (&discovered.render_icon, &discovered.project.icon) {
+                    (Some(request), Some(icon)) => {
+                        format!("render {} to {}", request.template, icon.display())
+                    }
+                    (None, Some(icon)) => icon.display().to_string(),
+                    (_, None) => String::new(),
+                };
+                vec![
+                    discovered.project.name.clone(),
+                    location,
+                    discovered.project.path.display().to_string(),
+                    icon,
+                ]
+            })
+            .collect();
+        print_table(&["Name", "Location", "Path", "Icon"], rows);
+
+        if !self.discovery.disabled.is_empty() || !self.discovery.unmatched_disabled.is_empty() {
+            println!();
+            println!("{}", style("Disabled Projects").bold());
+            let skipped = self
+                .discovery
+                .disabled
+                .iter()
+                .map(|path| vec!["skipped".to_string(), path.display().to_string()]);
+            let unmatched = self.discovery.unmatched_disabled.iter().map(|path| {
+                vec![
+                    style("no match").yellow().to_string(),
+                    path.display().to_string(),
+                ]
+            });
+            print_table(&["Status", "Path"], skipped.chain(unmatched).collect());
+        }
+
+        println!();
+        println!("{}", style("Shortcuts").bold());
+        let actions = [
+            (style("create").green(), &self.shortcuts.create),
+            (style("update").yellow(), &self.shortcuts.update),
+            (style("remove").red(), &self.shortcuts.remove),
+            (style("unchanged").dim(), &self.shortcuts.unchanged),
+            (style("keep").yellow(), &self.shortcuts.keep),
+        ];
+        let rows = actions
+            .iter()
+            .flat_map(|(action, shortcuts)| {
+                shortcuts.iter().map(move |shortcut| {
+                    vec![
+                        action.to_string(),
+                        shortcut.name.clone(),
+                        shortcut.path.display().to_string(),
+                    ]
+                })
+            })
+            .collect();
+        print_table(&["Action", "Name", "File"], rows);
+        println!("  {}", style(self.summary).dim());
+
+        if !self.discovery.failures.is_empty() {
+            println!();
+            println!("{}", style("Failures").red().bold());
+            let rows = self
+                .discovery
+                .failures
+                .iter()
+                .map(|failure| vec![failure.project.clone(), failure.error.clone()])
+                .collect();
+            print_table(&["Path", "Error"], rows);
+        }
+    }
+}
+
+pub fn print_table(headers: &[&str], rows: Vec<Vec<String>>) {
+    let mut widths: Vec<usize> = headers.iter().map(|header| header.len()).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(measure_text_width(cell));
+        }
+    }
+    let print_row = |cells: Vec<String>| {
+        let line: Vec<_> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| pad_str(cell, *width, Alignment::Left, None).into_owned())
+            .collect();
+        println!("  {}", line.join("  ").trim_end());
+    };
+    print_row(
+        headers
+            .iter()
+            .map(|header| style(header).underlined().to_string())
+            .collect(),
+    );
+    if rows.is_empty() {
+        println!("  {}", style("Nothing").dim());
+    }
+    for row in rows {
+        print_row(row);
+    }
+}
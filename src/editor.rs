@@ -0,0 +1,501 @@
+//! The programs projects can be opened in
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use directories::BaseDirs;
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::discovery::MarkerSet;
+
+/// Replaced with the project directory in [Editor::arguments]
+pub const PATH_PLACEHOLDER: &str = "{path}";
+/// The id of VS Code. Its executable is `vs_code_path`
+pub const VS_CODE: &str = "code";
+
+/// A program that opens a project
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Editor {
+    /// Selects the editor in the config. Such as `code` or `idea`
+    pub id: String,
+    /// Shown in shortcut descriptions. `Open {project} in {name}`
+    pub name: String,
+    /// A path or the name of a program on the `PATH`
+    pub executable: PathBuf,
+    /// Arguments passed to the executable. [PATH_PLACEHOLDER] is replaced with the project directory
+    #[serde(default = "default_arguments")]
+    pub arguments: Vec<String>,
+    /// Opens `.code-workspace` files. Shortcuts then open the workspace of a project instead of its directory.
+    ///
+    /// Defaults to the preset with the same id, otherwise false
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workspaces: Option<bool>,
+}
+fn default_arguments() -> Vec<String> {
+    vec![PATH_PLACEHOLDER.to_string()]
+}
+impl Editor {
+    /// The executable with a program name looked up on the `PATH`
+    pub fn resolve_executable(&self) -> PathBuf {
+        if self.executable.components().count() != 1 {
+            return self.executable.clone();
+        }
+        which::which(&self.executable).unwrap_or_else(|error| {
+            debug!("Unable to find {}: {}", self.executable.display(), error);
+            self.executable.clone()
+        })
+    }
+    /// The arguments that open `project`
+    pub fn arguments_for(&self, project: &Path) -> Vec<String> {
+        let project = project.to_string_lossy();
+        self.arguments
+            .iter()
+            .map(|argument| argument.replace(PATH_PLACEHOLDER, &project))
+            .collect()
+    }
+}
+
+/// Opens projects containing a file in an editor. Such as `pom.xml` in IntelliJ IDEA
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EditorRule {
+    /// File names or glob patterns. The rule applies if the project contains any of them
+    pub markers: Vec<String>,
+    /// The id of the editor
+    pub editor: String,
+}
+/// Picks the editor of a project with the `editor_rules` of the config
+#[derive(Debug, Clone, Default)]
+pub struct EditorRules {
+    rules: Vec<(MarkerSet, String)>,
+}
+impl EditorRules {
+    pub fn new(rules: &[EditorRule]) -> anyhow::Result<Self> {
+        let rules = rules
+            .iter()
+            .map(|rule| {
+                let markers = MarkerSet::new(rule.markers.iter().map(String::as_str))?;
+                Ok((markers, rule.editor.clone()))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self { rules })
+    }
+    /// The editor of the first rule matching the project directory
+    pub fn select(&self, project: &Path) -> Option<&str> {
+        self.rules
+            .iter()
+            .find(|(markers, _)| markers.matches(project))
+            .map(|(_, editor)| editor.as_str())
+    }
+}
+
+/// An editor that can be used without configuring it
+struct Preset {
+    id: &'static str,
+    name: &'static str,
+    executable: &'static str,
+    arguments: &'static [&'static str],
+    /// A program that has to be on the `PATH` besides the executable
+    requires: Option<&'static str>,
+    workspaces: bool,
+}
+impl Preset {
+    fn to_editor(&self) -> Editor {
+        Editor {
+            id: self.id.to_string(),
+            name: self.name.to_string(),
+            executable: PathBuf::from(self.executable),
+            arguments: self.arguments.iter().map(|a| a.to_string()).collect(),
+            workspaces: Some(self.workspaces),
+        }
+    }
+}
+/// Neovim has no window of its own, so it is started in a terminal
+#[cfg(target_os = "windows")]
+const NEOVIM: Preset = Preset {
+    id: "nvim",
+    name: "Neovim",
+    executable: "wt.exe",
+    arguments: &["nvim", PATH_PLACEHOLDER],
+    requires: Some("nvim"),
+    workspaces: false,
+};
+/// `x-terminal-emulator` only exists on Debian and the distributions based on it.
+/// Elsewhere the preset is not detected and an editor with the id `nvim` has to start the terminal
+#[cfg(not(target_os = "windows"))]
+const NEOVIM: Preset = Preset {
+    id: "nvim",
+    name: "Neovim",
+    executable: "x-terminal-emulator",
+    arguments: &["-e", "nvim", PATH_PLACEHOLDER],
+    requires: Some("nvim"),
+    workspaces: false,
+};
+const PRESETS: &[Preset] = &[
+    Preset {
+        id: VS_CODE,
+        name: "VS Code",
+        executable: "code",
+        arguments: &[PATH_PLACEHOLDER],
+        requires: None,
+        workspaces: true,
+    },
+    Preset {
+        id: "code-insiders",
+        name: "VS Code Insiders",
+        executable: "code-insiders",
+        arguments: &[PATH_PLACEHOLDER],
+        requires: None,
+        workspaces: true,
+    },
+    Preset {
+        id: "codium",
+        name: "VSCodium",
+        executable: "codium",
+        arguments: &[PATH_PLACEHOLDER],
+        requires: None,
+        workspaces: true,
+    },
+    Preset {
+        id: "zed",
+        name: "Zed",
+        executable: "zed",
+        arguments: &[PATH_PLACEHOLDER],
+        requires: None,
+        workspaces: false,
+    },
+    Preset {
+        id: "subl",
+        name: "Sublime Text",
+        executable: "subl",
+        arguments: &["--new-window", PATH_PLACEHOLDER],
+        requires: None,
+        workspaces: false,
+    },
+    Preset {
+        id: "idea",
+        name: "IntelliJ IDEA",
+        executable: "idea",
+        arguments: &[PATH_PLACEHOLDER],
+        requires: None,
+        workspaces: false,
+    },
+    Preset {
+        id: "pycharm",
+        name: "PyCharm",
+        executable: "pycharm",
+        arguments: &[PATH_PLACEHOLDER],
+        requires: None,
+        workspaces: false,
+    },
+    Preset {
+        id: "webstorm",
+        name: "WebStorm",
+        executable: "webstorm",
+        arguments: &[PATH_PLACEHOLDER],
+        requires: None,
+        workspaces: false,
+    },
+    Preset {
+        id: "goland",
+        name: "GoLand",
+        executable: "goland",
+        arguments: &[PATH_PLACEHOLDER],
+        requires: None,
+        workspaces: false,
+    },
+    Preset {
+        id: "clion",
+        name: "CLion",
+        executable: "clion",
+        arguments: &[PATH_PLACEHOLDER],
+        requires: None,
+        workspaces: false,
+    },
+    Preset {
+        id: "rustrover",
+        name: "RustRover",
+        executable: "rustrover",
+        arguments: &[PATH_PLACEHOLDER],
+        requires: None,
+        workspaces: false,
+    },
+    NEOVIM,
+];
+/// The built in editors
+pub fn presets() -> Vec<Editor> {
+    PRESETS.iter().map(Preset::to_editor).collect()
+}
+
+/// How a detected editor was installed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstallSource {
+    Path,
+    User,
+    System,
+    #[cfg(not(target_os = "windows"))]
+    Flatpak,
+    #[cfg(not(target_os = "windows"))]
+    Snap,
+    /// The launcher scripts of JetBrains Toolbox
+    Toolbox,
+}
+impl Display for InstallSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InstallSource::Path => write!(f, "PATH"),
+            InstallSource::User => write!(f, "user install"),
+            InstallSource::System => write!(f, "system install"),
+            #[cfg(not(target_os = "windows"))]
+            InstallSource::Flatpak => write!(f, "Flatpak"),
+            #[cfg(not(target_os = "windows"))]
+            InstallSource::Snap => write!(f, "Snap"),
+            InstallSource::Toolbox => write!(f, "JetBrains Toolbox"),
+        }
+    }
+}
+/// A preset found on this machine
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DetectedEditor {
+    /// The preset with the found executable
+    pub editor: Editor,
+    pub source: InstallSource,
+}
+/// Finds the presets installed on this machine.
+///
+/// Checks the `PATH` and then the usual install locations. An editor can be found more than once
+pub fn detect_editors(base_dirs: &BaseDirs) -> Vec<DetectedEditor> {
+    detect_in(&install_locations(base_dirs))
+}
+fn detect_in(locations: &[(&str, InstallSource, PathBuf)]) -> Vec<DetectedEditor> {
+    let mut detected: Vec<DetectedEditor> = Vec::new();
+    for preset in PRESETS {
+        let on_path = preset
+            .requires
+            .is_none_or(|program| which::which(program).is_ok())
+            .then(|| which::which(preset.executable).ok())
+            .flatten()
+            .map(|executable| (InstallSource::Path, executable));
+        let installed = locations
+            .iter()
+            .filter(|(id, _, path)| *id == preset.id && path.is_file())
+            .map(|(_, source, path)| (*source, path.clone()));
+        let mut found: Vec<PathBuf> = Vec::new();
+        for (source, executable) in on_path.into_iter().chain(installed) {
+            // The PATH often links to one of the install locations
+            let canonical = executable.canonicalize().unwrap_or(executable.clone());
+            if found.contains(&canonical) {
+                continue;
+            }
+            found.push(canonical);
+            debug!("Found {} at {}", preset.name, executable.display());
+            detected.push(DetectedEditor {
+                editor: Editor {
+                    executable,
+                    ..preset.to_editor()
+                },
+                source,
+            });
+        }
+    }
+    detected
+}
+/// Product names of the JetBrains IDEs with presets. Used by Snap and Flatpak
+const JETBRAINS: &[(&str, &[&str])] = &[
+    (
+        "idea",
+        &["IntelliJ-IDEA-Community", "IntelliJ-IDEA-Ultimate"],
+    ),
+    ("pycharm", &["PyCharm-Community", "PyCharm-Professional"]),
+    ("webstorm", &["WebStorm"]),
+    ("goland", &["GoLand"]),
+    ("clion", &["CLion"]),
+    ("rustrover", &["RustRover"]),
+];
+/// The executables of editors installed outside of the `PATH`
+#[cfg(target_os = "windows")]
+fn install_locations(base_dirs: &BaseDirs) -> Vec<(&'static str, InstallSource, PathBuf)> {
+    let local_programs = base_dirs.data_local_dir().join("Programs");
+    let program_files = std::env::var_os("ProgramFiles")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("C:\\Program Files"));
+    let mut locations = Vec::new();
+    for (id, directory, executable) in [
+        (VS_CODE, "Microsoft VS Code", "Code.exe"),
+        (
+            "code-insiders",
+            "Microsoft VS Code Insiders",
+            "Code - Insiders.exe",
+        ),
+        ("codium", "VSCodium", "VSCodium.exe"),
+        ("zed", "Zed", "Zed.exe"),
+    ] {
+        let user = local_programs.join(directory).join(executable);
+        locations.push((id, InstallSource::User, user));
+        let system = program_files.join(directory).join(executable);
+        locations.push((id, InstallSource::System, system));
+    }
+    let sublime = program_files.join("Sublime Text").join("subl.exe");
+    locations.push(("subl", InstallSource::System, sublime));
+    let toolbox = base_dirs
+        .data_local_dir()
+        .join("JetBrains\\Toolbox\\scripts");
+    for (id, _) in JETBRAINS {
+        locations.push((
+            id,
+            InstallSource::Toolbox,
+            toolbox.join(format!("{id}.cmd")),
+        ));
+    }
+    locations
+}
+/// The executables of editors installed outside of the `PATH`
+#[cfg(not(target_os = "windows"))]
+fn install_locations(base_dirs: &BaseDirs) -> Vec<(&'static str, InstallSource, PathBuf)> {
+    let mut locations = Vec::new();
+    for (id, path) in [
+        (VS_CODE, "/usr/share/code/bin/code"),
+        (VS_CODE, "/usr/bin/code"),
+        (
+            "code-insiders",
+            "/usr/share/code-insiders/bin/code-insiders",
+        ),
+        ("code-insiders", "/usr/bin/code-insiders"),
+        ("codium", "/usr/share/codium/bin/codium"),
+        ("codium", "/usr/bin/codium"),
+        ("zed", "/usr/bin/zeditor"),
+        ("subl", "/opt/sublime_text/sublime_text"),
+    ] {
+        locations.push((id, InstallSource::System, PathBuf::from(path)));
+    }
+    let zed = base_dirs.home_dir().join(".local/bin/zed");
+    locations.push(("zed", InstallSource::User, zed));
+
+    let mut snaps = vec![
+        (VS_CODE, "code".to_string()),
+        ("code-insiders", "code-insiders".to_string()),
+        ("codium", "codium".to_string()),
+        ("subl", "subl".to_string()),
+    ];
+    let mut flatpaks = vec![
+        (VS_CODE, "com.visualstudio.code".to_string()),
+        ("codium", "com.vscodium.codium".to_string()),
+        ("zed", "dev.zed.Zed".to_string()),
+        ("subl", "com.sublimetext.three".to_string()),
+    ];
+    let toolbox = base_dirs.data_local_dir().join("JetBrains/Toolbox/scripts");
+    for (id, products) in JETBRAINS {
+        locations.push((id, InstallSource::Toolbox, toolbox.join(id)));
+        for product in *products {
+            snaps.push((id, product.to_lowercase()));
+            flatpaks.push((id, format!("com.jetbrains.{product}")));
+        }
+    }
+    for (id, snap) in snaps {
+        let path = Path::new("/snap/bin").join(snap);
+        locations.push((id, InstallSource::Snap, path));
+    }
+    let flatpak_exports = [
+        PathBuf::from("/var/lib/flatpak/exports/bin"),
+        base_dirs.data_local_dir().join("flatpak/exports/bin"),
+    ];
+    for (id, app) in flatpaks {
+        for exports in &flatpak_exports {
+            locations.push((id, InstallSource::Flatpak, exports.join(&app)));
+        }
+    }
+    locations
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{Editor, EditorRule, EditorRules, InstallSource};
+
+    #[test]
+    fn arguments() {
+        let editor = Editor {
+            id: "subl".to_string(),
+            name: "Sublime Text".to_string(),
+            executable: PathBuf::from("/opt/sublime_text/sublime_text"),
+            arguments: vec!["--new-window".to_string(), "{path}".to_string()],
+            workspaces: None,
+        };
+        assert_eq!(
+            editor.arguments_for(Path::new("/code/my project")),
+            ["--new-window", "/code/my project"]
+        );
+        assert_eq!(
+            editor.resolve_executable(),
+            PathBuf::from("/opt/sublime_text/sublime_text")
+        );
+        let ids: Vec<_> = super::presets().into_iter().map(|e| e.id).collect();
+        let mut unique = ids.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(ids.len(), unique.len());
+    }
+    #[test]
+    fn rules() {
+        let root = tempfile::tempdir().unwrap();
+        for (project, file) in [("java", "pom.xml"), ("web", "web.code-workspace")] {
+            std::fs::create_dir(root.path().join(project)).unwrap();
+            std::fs::write(root.path().join(project).join(file), "").unwrap();
+        }
+        std::fs::create_dir(root.path().join("other")).unwrap();
+        let rule = |markers: &[&str], editor: &str| EditorRule {
+            markers: markers.iter().map(|m| m.to_string()).collect(),
+            editor: editor.to_string(),
+        };
+        let rules = EditorRules::new(&[
+            rule(&["pom.xml", "build.gradle"], "idea"),
+            rule(&["*.code-workspace"], "code"),
+            rule(&["pom.xml"], "nvim"),
+        ])
+        .unwrap();
+        assert_eq!(rules.select(&root.path().join("java")), Some("idea"));
+        assert_eq!(rules.select(&root.path().join("web")), Some("code"));
+        assert_eq!(rules.select(&root.path().join("other")), None);
+        assert!(EditorRules::new(&[rule(&["[pom"], "idea")]).is_err());
+    }
+    #[test]
+    fn detect() {
+        let root = tempfile::tempdir().unwrap();
+        let file = |name: &str| {
+            let path = root.path().join(name);
+            std::fs::write(&path, "").unwrap();
+            path
+        };
+        let code = file("code");
+        let idea = file("idea");
+        let link = root.path().join("code-link");
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&code, &link).unwrap();
+        let locations = [
+            ("code", InstallSource::User, code.clone()),
+            ("code", InstallSource::System, link),
+            ("code", InstallSource::System, root.path().join("missing")),
+            ("idea", InstallSource::Toolbox, idea.clone()),
+        ];
+        let detected: Vec<_> = super::detect_in(&locations)
+            .into_iter()
+            .filter(|detected| detected.editor.executable.starts_with(root.path()))
+            .map(|detected| {
+                (
+                    detected.editor.id,
+                    detected.editor.executable,
+                    detected.source,
+                )
+            })
+            .collect();
+        assert_eq!(
+            detected,
+            [
+                ("code".to_string(), code, InstallSource::User),
+                ("idea".to_string(), idea, InstallSource::Toolbox)
+            ]
+        );
+    }
+}
@@ -0,0 +1,111 @@
+//! Writes Apple Icon Image (.icns) files.
+//!
+//! Every image is stored as a PNG, which macOS supports for all of the types below since 10.7.
+use std::io::{self, Write};
+
+/// An image type in an ICNS file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IconType {
+    pub ostype: [u8; 4],
+    /// The size in points
+    pub size: u32,
+    /// 2 for @2x (Retina) images
+    pub scale: u32,
+}
+impl IconType {
+    const fn new(ostype: &[u8; 4], size: u32, scale: u32) -> Self {
+        Self {
+            ostype: *ostype,
+            size,
+            scale,
+        }
+    }
+    /// The width and height of the image in pixels
+    pub fn pixels(&self) -> u32 {
+        self.size * self.scale
+    }
+}
+/// The standard sizes from 16 to 512 points with their @2x variants
+pub const ICON_TYPES: &[IconType] = &[
+    IconType::new(b"icp4", 16, 1),
+    IconType::new(b"ic11", 16, 2),
+    IconType::new(b"icp5", 32, 1),
+    IconType::new(b"ic12", 32, 2),
+    IconType::new(b"ic07", 128, 1),
+    IconType::new(b"ic13", 128, 2),
+    IconType::new(b"ic08", 256, 1),
+    IconType::new(b"ic14", 256, 2),
+    IconType::new(b"ic09", 512, 1),
+    IconType::new(b"ic10", 512, 2),
+];
+/// Every element starts with the OSType and the length
+const ELEMENT_HEADER_SIZE: u32 = 8;
+
+#[derive(Debug, Default)]
+pub struct Icns {
+    elements: Vec<(IconType, Vec<u8>)>,
+}
+impl Icns {
+    /// Adds a PNG encoded image. It must be [IconType::pixels] wide and tall
+    pub fn add(&mut self, icon_type: IconType, png: Vec<u8>) {
+        self.elements.push((icon_type, png));
+    }
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        let length: u32 = self
+            .elements
+            .iter()
+            .map(|(_, png)| ELEMENT_HEADER_SIZE + png.len() as u32)
+            .sum();
+        writer.write_all(b"icns")?;
+        writer.write_all(&(ELEMENT_HEADER_SIZE + length).to_be_bytes())?;
+        for (icon_type, png) in &self.elements {
+            writer.write_all(&icon_type.ostype)?;
+            writer.write_all(&(ELEMENT_HEADER_SIZE + png.len() as u32).to_be_bytes())?;
+            writer.write_all(png)?;
+        }
+        Ok(())
+    }
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.write_to(&mut bytes)
+            .expect("Writing to a Vec does not fail");
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Icns, ICON_TYPES};
+
+    #[test]
+    fn round_trip() {
+        let mut icns = Icns::default();
+        for icon_type in ICON_TYPES {
+            let size = icon_type.pixels();
+            let mut pixmap = tiny_skia::Pixmap::new(size, size).unwrap();
+            pixmap.fill(tiny_skia::Color::from_rgba8(206, 66, 43, 255));
+            icns.add(*icon_type, pixmap.encode_png().unwrap());
+        }
+        let bytes = icns.to_bytes();
+        assert_eq!(&bytes[..4], b"icns");
+        assert_eq!(
+            u32::from_be_bytes(bytes[4..8].try_into().unwrap()) as usize,
+            bytes.len()
+        );
+
+        let family = icns::IconFamily::read(std::io::Cursor::new(bytes)).unwrap();
+        assert_eq!(family.elements.len(), ICON_TYPES.len());
+        for (element, icon_type) in family.elements.iter().zip(ICON_TYPES) {
+            assert_eq!(element.ostype.0, icon_type.ostype);
+            let parsed = element.icon_type().expect("Unknown icon type");
+            assert_eq!(parsed.pixel_width(), icon_type.pixels());
+            assert_eq!(parsed.pixel_density(), icon_type.scale);
+            let image = element.decode_image().unwrap();
+            assert_eq!(
+                (image.width(), image.height()),
+                (icon_type.pixels(), icon_type.pixels())
+            );
+            assert_eq!(&image.data()[..4], &[206, 66, 43, 255]);
+        }
+    }
+}
@@ -1,10 +1,23 @@
-use std::{fs::remove_file, path::PathBuf};
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    path::{Path, PathBuf},
+};
 
 use anyhow::Context;
 use log::debug;
+use once_cell::sync::Lazy;
 use rust_embed::RustEmbed;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use unicode_segmentation::UnicodeSegmentation;
 
-use crate::{config::IconStyle, AppState};
+use crate::{
+    config::{IconStyle, Project},
+    discovery::DiscoveredProject,
+    icns::{self, Icns},
+    utils::{hash_field, to_hex, unique_slug},
+    AppState,
+};
 
 #[derive(RustEmbed)]
 #[folder = "$CARGO_MANIFEST_DIR/icons"]
@@ -14,57 +27,652 @@ struct Icons;
 #[folder = "$CARGO_MANIFEST_DIR/fonts"]
 struct Fonts;
 
-pub fn build_icon(style: IconStyle, name: &str, app_state: &AppState) -> anyhow::Result<PathBuf> {
-    if !app_state.project_dirs.cache_dir().exists() {
-        std::fs::create_dir_all(app_state.project_dirs.cache_dir())?;
+/// The embedded template used for each [IconStyle]
+const TEMPLATES: &[(IconStyle, &str)] = &[
+    (IconStyle::Default, "default"),
+    (IconStyle::Cargo, "cargo"),
+    (IconStyle::TypeScript, "typescript"),
+    (IconStyle::JavaScript, "javascript"),
+    (IconStyle::Python, "python"),
+    (IconStyle::Go, "go"),
+    (IconStyle::Java, "java"),
+    (IconStyle::Initials, "initials"),
+    // Only used if the style was never resolved
+    (IconStyle::Auto, "default"),
+];
+/// Replaced with the first letter of the project name. See [TemplateVariables] for the others
+const INITIAL_PLACEHOLDER: &str = "{{INITIAL}}";
+/// Files that identify the language of a project. The first match wins
+const LANGUAGE_MARKERS: &[(&str, IconStyle)] = &[
+    ("Cargo.toml", IconStyle::Cargo),
+    ("go.mod", IconStyle::Go),
+    ("tsconfig.json", IconStyle::TypeScript),
+    ("deno.json", IconStyle::TypeScript),
+    ("package.json", IconStyle::JavaScript),
+    ("pyproject.toml", IconStyle::Python),
+    ("setup.py", IconStyle::Python),
+    ("requirements.txt", IconStyle::Python),
+    ("Pipfile", IconStyle::Python),
+    ("pom.xml", IconStyle::Java),
+    ("build.gradle", IconStyle::Java),
+    ("build.gradle.kts", IconStyle::Java),
+];
+impl IconStyle {
+    /// Replaces [IconStyle::Auto] with the style matching the project's language
+    ///
+    /// Falls back to [IconStyle::Default] if the language is unknown
+    pub fn resolve(self, project: &Path) -> IconStyle {
+        if self != IconStyle::Auto {
+            return self;
+        }
+        LANGUAGE_MARKERS
+            .iter()
+            .find(|(marker, _)| project.join(marker).is_file())
+            .map(|(_, style)| *style)
+            .unwrap_or_default()
+    }
+    /// The name of the template used for this style
+    pub fn template(self) -> &'static str {
+        TEMPLATES
+            .iter()
+            .find(|(template_style, _)| *template_style == self)
+            .map(|(_, name)| *name)
+            .expect("Every IconStyle has a template")
+    }
+}
+/// User templates are stored in `templates` next to `config.toml`
+pub fn templates_dir(app_state: &AppState) -> PathBuf {
+    app_state.project_dirs.config_dir().join("templates")
+}
+/// Loads a template by name.
+///
+/// A template in [templates_dir] overrides the embedded template with the same name
+fn load_template(name: &str, templates_dir: &Path) -> anyhow::Result<String> {
+    if name.is_empty() || name.contains(['/', '\\', '.']) {
+        return Err(anyhow::anyhow!("Invalid template name {:?}", name));
+    }
+    let user_template = templates_dir.join(format!("{name}.svg"));
+    if user_template.exists() {
+        debug!("Using template {}", user_template.display());
+        let svg = std::fs::read_to_string(&user_template).context(format!(
+            "Unable to read template {}",
+            user_template.display()
+        ))?;
+        ico::parse_template(&svg).context(format!(
+            "Template {} is not a valid SVG",
+            user_template.display()
+        ))?;
+        return Ok(svg);
     }
+    let embedded = Icons::get(&format!("{name}.svg")).ok_or_else(|| {
+        anyhow::anyhow!("No template named {} in {}", name, templates_dir.display())
+    })?;
+    String::from_utf8(embedded.data.to_vec()).context("Invalid icon file")
+}
+/// Checks that a template exists and is a valid SVG
+pub fn validate_template(name: &str, app_state: &AppState) -> anyhow::Result<()> {
+    load_template(name, &templates_dir(app_state)).map(|_| ())
+}
+
+/// Copies a user provided icon into the data directory.
+///
+/// SVG and PNG files are converted to ICO. ICO files are copied as is
+pub fn import_icon(source: &Path, name: &str, app_state: &AppState) -> anyhow::Result<PathBuf> {
+    let extension = source
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+    let data = std::fs::read(source).context(format!("Unable to read {}", source.display()))?;
+    let (image, icon) = match extension.as_deref() {
+        Some("svg") => {
+            let svg = String::from_utf8(data).context("SVG is not UTF-8")?;
+            let image = ico::Image::from_svg(&svg, app_state.config.system_fonts)?;
+            let icon = image.to_ico(&app_state.config.icon_sizes)?;
+            (image, icon)
+        }
+        Some("png") => (ico::Image::from_png(&data)?, ico::png_to_ico(&data)?),
+        Some("ico") => (ico::Image::from_ico(&data)?, data),
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Unsupported icon {}. Icons must be SVG, PNG or ICO files",
+                source.display()
+            ))
+        }
+    };
     if !app_state.project_dirs.data_dir().exists() {
         std::fs::create_dir_all(app_state.project_dirs.data_dir())?;
     }
-    let icon_file = match style {
-        IconStyle::Default => Icons::get("default.svg").expect("Missing ICON"),
-        IconStyle::Cargo => Icons::get("default.svg").expect("Missing ICON"),
+    let ico_path = icon_path(name, app_state);
+    std::fs::write(&ico_path, &icon)?;
+    debug!("Imported {} to {}", source.display(), ico_path.display());
+    write_other_formats(&image, name, &ico_key(&icon), app_state)?;
+    Ok(ico_path)
+}
+/// Where the icon for the project is stored
+pub fn icon_path(name: &str, app_state: &AppState) -> PathBuf {
+    app_state
+        .project_dirs
+        .data_dir()
+        .join(format!("{}.ico", name))
+}
+/// An icon that has to be rendered for a project
+#[derive(Debug, Clone, Serialize)]
+pub struct IconRequest {
+    pub template: String,
+    /// The name of the project without the location
+    pub name: String,
+    /// The name of the [ProjectLocation](crate::config::ProjectLocation) the project was found in
+    pub location: Option<String>,
+}
+/// Finds the icon files that are not used by any of the projects.
+///
+/// Looks at the ICO, ICNS and [formats_key_path] files in the data directory, the icon cache, SVGs cached by older versions and our icons in the icon theme
+pub fn unused_icon_files(
+    projects: &[DiscoveredProject],
+    app_state: &AppState,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let mut referenced = HashSet::new();
+    let mut theme_names = HashSet::new();
+    for (name, icon) in kept_projects(projects, &app_state.config.projects) {
+        if let Some(icon) = icon {
+            referenced.insert(icon.to_path_buf());
+        }
+        referenced.insert(icns_path(name, app_state));
+        referenced.insert(formats_key_path(name, app_state));
+        theme_names.insert(theme_icon_name(name));
+    }
+    for discovered in projects {
+        let name = &discovered.project.name;
+        if let Some(request) = &discovered.render_icon {
+            match cache_key(request, name, app_state) {
+                Ok(key) => {
+                    referenced.insert(icon_cache_dir(app_state).join(format!("{key}.ico")));
+                }
+                Err(error) => debug!("No cached icon for {}: {:#}", name, error),
+            }
+        }
+    }
+    let is_unused = |path: &Path, extensions: &[&str]| {
+        let extension = path.extension().and_then(|extension| extension.to_str());
+        extension.is_some_and(|extension| extensions.contains(&extension))
+            && !referenced.contains(path)
     };
-    // Get First letter of name
-    let letter = name
-        .chars()
-        .next()
-        .unwrap_or('?')
-        .to_ascii_uppercase()
-        .to_string();
-    let cached_svg_icon = app_state.project_dirs.cache_dir().join(format!(
-        "{}-{}.svg",
-        letter,
-        AsRef::<str>::as_ref(&style)
-    ));
-    let svg = if !cached_svg_icon.exists() {
-        let icon_file = String::from_utf8(icon_file.data.to_vec()).context("Invalid icon file")?;
-        let icon_file = icon_file.replace(r#"{{INITIAL}}"#, letter.as_str());
-        std::fs::write(&cached_svg_icon, &icon_file)?;
-        icon_file
+    let mut unused = unused_files(app_state.project_dirs.data_dir(), |path| {
+        is_unused(path, &["ico", "icns", "formats"])
+    })?;
+    unused.extend(unused_files(&icon_cache_dir(app_state), |path| {
+        is_unused(path, &["ico"])
+    })?);
+    unused.extend(legacy_cache_files(app_state.project_dirs.cache_dir())?);
+    let theme_dir = icon_theme_dir()?;
+    if theme_dir.exists() {
+        for size in theme_dir.read_dir()? {
+            let apps = size?.path().join("apps");
+            unused.extend(unused_files(&apps, |path| {
+                let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+                stem.starts_with(THEME_ICON_PREFIX) && !theme_names.contains(stem.as_ref())
+            })?);
+        }
+    }
+    Ok(unused)
+}
+/// The names and icons of the projects whose icon files are kept.
+///
+/// Every project in the config counts, even disabled ones, because an imported icon is the only copy
+fn kept_projects<'a>(
+    discovered: &'a [DiscoveredProject],
+    added: &'a [Project],
+) -> Vec<(&'a str, Option<&'a Path>)> {
+    discovered
+        .iter()
+        .map(|discovered| &discovered.project)
+        .chain(added)
+        .map(|project| (project.name.as_str(), project.icon.as_deref()))
+        .collect()
+}
+/// Removes the icons cached before they were stored in [icon_cache_dir].
+///
+/// They were named after the initial and style, such as `A-Default.svg`, and are never read again.
+/// Only runs while the cache directory does not exist yet
+pub fn remove_legacy_cache(app_state: &AppState) -> anyhow::Result<()> {
+    let cache_dir = icon_cache_dir(app_state);
+    if cache_dir.exists() {
+        return Ok(());
+    }
+    for file in legacy_cache_files(app_state.project_dirs.cache_dir())? {
+        debug!("Removing old cached icon {}", file.display());
+        std::fs::remove_file(&file).context(format!("Unable to remove {}", file.display()))?;
+    }
+    std::fs::create_dir_all(&cache_dir).context(format!("Unable to create {}", cache_dir.display()))
+}
+/// SVG and ICO files in the root of the cache directory. Left by older versions
+fn legacy_cache_files(cache_dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    unused_files(cache_dir, |path| {
+        path.extension()
+            .is_some_and(|extension| extension == "svg" || extension == "ico")
+    })
+}
+/// The files directly inside of the directory that are unused
+fn unused_files(dir: &Path, is_unused: impl Fn(&Path) -> bool) -> anyhow::Result<Vec<PathBuf>> {
+    let mut unused = Vec::new();
+    if !dir.is_dir() {
+        return Ok(unused);
+    }
+    for entry in dir
+        .read_dir()
+        .context(format!("Unable to read {}", dir.display()))?
+    {
+        let entry = entry?;
+        if entry.file_type()?.is_file() && is_unused(&entry.path()) {
+            unused.push(entry.path());
+        }
+    }
+    Ok(unused)
+}
+/// Rendered icons are stored in the cache directory by [cache_key]
+fn icon_cache_dir(app_state: &AppState) -> PathBuf {
+    app_state.project_dirs.cache_dir().join("icons")
+}
+/// Bump when a change to the rendering changes the output
+const RENDER_VERSION: u32 = 2;
+/// A hash of the names and contents of the bundled fonts
+static FONT_SET_KEY: Lazy<[u8; 32]> = Lazy::new(|| {
+    let mut hasher = Sha256::new();
+    for file in Fonts::iter() {
+        let font = Fonts::get(file.as_ref()).expect("Embedded font");
+        hasher.update(file.as_bytes());
+        hasher.update(font.metadata.sha256_hash());
+    }
+    hasher.finalize().into()
+});
+/// [FONT_SET_KEY] followed by the families, path and modification time of every system font.
+///
+/// Installing, removing or updating a system font changes it
+static SYSTEM_FONT_SET_KEY: Lazy<[u8; 32]> = Lazy::new(|| {
+    let mut hasher = Sha256::new();
+    let mut field = |value: &[u8]| hash_field(&mut hasher, value);
+    field(FONT_SET_KEY.as_slice());
+    for face in ico::fonts(true).faces() {
+        // The bundled fonts are loaded from memory and already part of FONT_SET_KEY
+        let usvg::fontdb::Source::File(path) = &face.source else {
+            continue;
+        };
+        for (family, _) in &face.families {
+            field(family.as_bytes());
+        }
+        field(path.to_string_lossy().as_bytes());
+        field(&face.index.to_le_bytes());
+        let modified = std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|modified| modified.as_nanos())
+            .unwrap_or_default();
+        field(&modified.to_le_bytes());
+    }
+    hasher.finalize().into()
+});
+/// A hash of everything that changes the rendered ICO.
+///
+/// The template, the values of its placeholders, the fonts and the sizes
+fn render_key(
+    template: &str,
+    variables: &TemplateVariables,
+    sizes: &[u32],
+    system_fonts: bool,
+) -> String {
+    let mut hasher = Sha256::new();
+    let mut field = |value: &[u8]| hash_field(&mut hasher, value);
+    field(&RENDER_VERSION.to_le_bytes());
+    field(template.as_bytes());
+    for (placeholder, value) in variables.placeholders() {
+        field(placeholder.as_bytes());
+        field(value.as_bytes());
+    }
+    if system_fonts {
+        field(SYSTEM_FONT_SET_KEY.as_slice());
     } else {
-        debug!("Found cached icon at {:?}", cached_svg_icon);
-        std::fs::read_to_string(&cached_svg_icon).context("Unable to read cached icon")?
-    };
-    let ico_path = app_state
+        field(FONT_SET_KEY.as_slice());
+    }
+    for size in sizes {
+        field(&size.to_le_bytes());
+    }
+    to_hex(&hasher.finalize())
+}
+/// The key of the project's icon in the cache
+pub fn cache_key(
+    request: &IconRequest,
+    name: &str,
+    app_state: &AppState,
+) -> anyhow::Result<String> {
+    let template = load_template(&request.template, &templates_dir(app_state))?;
+    let variables = TemplateVariables::new(request, name);
+    Ok(render_key(
+        &template,
+        &variables,
+        &app_state.config.icon_sizes,
+        app_state.config.system_fonts,
+    ))
+}
+/// Renders the template for the project and writes it to [icon_path]
+///
+/// If the cache has an icon with the same [cache_key] it is reused
+pub fn build_icon(
+    request: &IconRequest,
+    name: &str,
+    app_state: &AppState,
+) -> anyhow::Result<PathBuf> {
+    let template = load_template(&request.template, &templates_dir(app_state))?;
+    let variables = TemplateVariables::new(request, name);
+    let key = render_key(
+        &template,
+        &variables,
+        &app_state.config.icon_sizes,
+        app_state.config.system_fonts,
+    );
+    let cached_ico = icon_cache_dir(app_state).join(format!("{key}.ico"));
+    let ico_path = icon_path(name, app_state);
+    for dir in [
+        icon_cache_dir(app_state).as_path(),
+        app_state.project_dirs.data_dir(),
+    ] {
+        if !dir.exists() {
+            std::fs::create_dir_all(dir)?;
+        }
+    }
+    if cached_ico.exists() && !other_formats_outdated(name, &key, app_state) {
+        let cached = std::fs::read(&cached_ico).context("Unable to read cached icon")?;
+        if std::fs::read(&ico_path).ok().as_ref() != Some(&cached) {
+            std::fs::write(&ico_path, cached)?;
+        }
+        debug!("Reusing cached icon {:?}", cached_ico);
+        return Ok(ico_path);
+    }
+    let image = ico::Image::from_template(&template, &variables, app_state.config.system_fonts)?;
+    let icon = image.to_ico(&app_state.config.icon_sizes)?;
+    std::fs::write(&ico_path, &icon)?;
+    write_other_formats(&image, name, &key, app_state)?;
+    // Written last so a failure above renders the icon again next time
+    std::fs::write(&cached_ico, &icon)?;
+    Ok(ico_path)
+}
+/// Checks if any of the formats enabled besides ICO are missing or were written from another icon
+fn other_formats_outdated(name: &str, key: &str, app_state: &AppState) -> bool {
+    if !app_state.config.icon_theme && !app_state.config.icns {
+        return false;
+    }
+    let written_from = std::fs::read_to_string(formats_key_path(name, app_state)).ok();
+    let theme_missing = app_state.config.icon_theme
+        && theme_icon_files(name, app_state)
+            .map(|files| files.iter().any(|file| !file.exists()))
+            .unwrap_or(true);
+    let icns_missing = app_state.config.icns && !icns_path(name, app_state).exists();
+    written_from.as_deref() != Some(key) || theme_missing || icns_missing
+}
+/// Writes the formats enabled besides ICO. The hicolor theme icons and the ICNS file.
+///
+/// `key` identifies the icon they are written from and is stored in [formats_key_path]
+fn write_other_formats(
+    image: &ico::Image,
+    name: &str,
+    key: &str,
+    app_state: &AppState,
+) -> anyhow::Result<()> {
+    if app_state.config.icon_theme {
+        install_theme_icons(
+            image,
+            &theme_icon_name(name),
+            &app_state.config.icon_sizes,
+            &icon_theme_dir()?,
+        )?;
+    }
+    if app_state.config.icns {
+        let icns_path = icns_path(name, app_state);
+        std::fs::write(&icns_path, build_icns(image)?)
+            .context(format!("Unable to write {}", icns_path.display()))?;
+    }
+    let key_path = formats_key_path(name, app_state);
+    std::fs::write(&key_path, key).context(format!("Unable to write {}", key_path.display()))
+}
+/// Stores the key of the icon the other formats of the project were written from.
+///
+/// The [cache_key] for rendered icons and [ico_key] for imported ones
+fn formats_key_path(name: &str, app_state: &AppState) -> PathBuf {
+    app_state
         .project_dirs
         .data_dir()
-        .join(format!("{}.ico", name));
-    if ico_path.exists() {
-        remove_file(&ico_path)?;
+        .join(format!("{}.formats", name))
+}
+/// A hash of an imported ICO
+fn ico_key(ico: &[u8]) -> String {
+    to_hex(&Sha256::digest(ico))
+}
+fn icns_path(name: &str, app_state: &AppState) -> PathBuf {
+    app_state
+        .project_dirs
+        .data_dir()
+        .join(format!("{}.icns", name))
+}
+/// The PNGs in the icon theme for each of the sizes
+fn theme_icon_files(name: &str, app_state: &AppState) -> anyhow::Result<Vec<PathBuf>> {
+    let theme_dir = icon_theme_dir()?;
+    let icon_name = theme_icon_name(name);
+    Ok(app_state
+        .config
+        .icon_sizes
+        .iter()
+        .map(|size| {
+            theme_dir
+                .join(format!("{size}x{size}"))
+                .join("apps")
+                .join(format!("{icon_name}.png"))
+        })
+        .collect())
+}
+/// Renders every size in [icns::ICON_TYPES]
+fn build_icns(image: &ico::Image) -> anyhow::Result<Vec<u8>> {
+    let mut rendered: HashMap<u32, Vec<u8>> = HashMap::new();
+    let mut icns = Icns::default();
+    for icon_type in icns::ICON_TYPES {
+        let png = match rendered.entry(icon_type.pixels()) {
+            Entry::Occupied(png) => png.get().clone(),
+            Entry::Vacant(entry) => {
+                let png = image.render(icon_type.pixels())?.encode_png()?;
+                entry.insert(png).clone()
+            }
+        };
+        icns.add(*icon_type, png);
     }
-    let icon = ico::svg_to_ico(svg)?;
-    std::fs::write(&ico_path, icon)?;
-    Ok(ico_path)
+    Ok(icns.to_bytes())
+}
+/// The name of the project's icon in the hicolor icon theme.
+///
+/// The hash keeps names unique after the unsupported characters are replaced
+pub fn theme_icon_name(name: &str) -> String {
+    format!("{}{}", THEME_ICON_PREFIX, unique_slug(name))
+}
+/// Every icon we install into the icon theme starts with this
+const THEME_ICON_PREFIX: &str = "auto_project-";
+/// Writes the other formats of an existing ICO if they are outdated.
+///
+/// Such as for an icon imported before `icon_theme` was enabled, or one that was replaced
+pub fn ensure_other_formats(icon: &Path, name: &str, app_state: &AppState) -> anyhow::Result<()> {
+    if !app_state.config.icon_theme && !app_state.config.icns {
+        return Ok(());
+    }
+    let data = std::fs::read(icon).context(format!("Unable to read {}", icon.display()))?;
+    let key = ico_key(&data);
+    if !other_formats_outdated(name, &key, app_state) {
+        return Ok(());
+    }
+    let image = ico::Image::from_ico(&data)?;
+    write_other_formats(&image, name, &key, app_state)
+}
+/// `$XDG_DATA_HOME/icons/hicolor`
+fn icon_theme_dir() -> anyhow::Result<PathBuf> {
+    let base_dirs = directories::BaseDirs::new().context("Unable to Locate User Directories?")?;
+    Ok(base_dirs.data_dir().join("icons").join("hicolor"))
+}
+/// Writes a PNG for each size and, for SVG icons, a scalable icon into the icon theme
+fn install_theme_icons(
+    image: &ico::Image,
+    icon_name: &str,
+    sizes: &[u32],
+    theme_dir: &Path,
+) -> anyhow::Result<()> {
+    for size in sizes {
+        let apps = theme_dir.join(format!("{size}x{size}")).join("apps");
+        std::fs::create_dir_all(&apps).context(format!("Unable to create {}", apps.display()))?;
+        let png = image.render(*size)?.encode_png()?;
+        std::fs::write(apps.join(format!("{icon_name}.png")), png)?;
+    }
+    if let Some(svg) = image.to_svg() {
+        let apps = theme_dir.join("scalable").join("apps");
+        std::fs::create_dir_all(&apps).context(format!("Unable to create {}", apps.display()))?;
+        std::fs::write(apps.join(format!("{icon_name}.svg")), svg)?;
+    }
+    debug!("Installed {} into {}", icon_name, theme_dir.display());
+    Ok(())
+}
+/// The values of the placeholders in a template
+#[derive(Debug, PartialEq)]
+struct TemplateVariables {
+    /// `{{INITIAL}}` The first letter of the name
+    initial: String,
+    /// `{{INITIALS}}` The first letters of the first two words of the name
+    initials: String,
+    /// `{{NAME}}`
+    name: String,
+    /// `{{LOCATION}}`
+    location: String,
+    /// `{{COLOR}}` A color picked from the hash of the project name
+    color: String,
+    /// `{{ACCENT}}` A color that goes with `{{COLOR}}`
+    accent: String,
+}
+impl TemplateVariables {
+    fn new(request: &IconRequest, project_name: &str) -> Self {
+        let words = split_words(&request.name);
+        let initials: String = words.iter().take(2).map(|word| initial(word)).collect();
+        let hash = Sha256::digest(project_name.as_bytes());
+        let hue = u16::from_be_bytes([hash[0], hash[1]]) as f32 % 360.0;
+        Self {
+            initial: initial(&request.name),
+            initials: if initials.is_empty() {
+                initial(&request.name)
+            } else {
+                initials
+            },
+            name: escape_xml(&request.name),
+            location: escape_xml(request.location.as_deref().unwrap_or_default()),
+            color: hsl_to_hex(hue, 0.6, 0.45),
+            accent: hsl_to_hex((hue + 150.0) % 360.0, 0.7, 0.65),
+        }
+    }
+    fn placeholders(&self) -> [(&'static str, &str); 6] {
+        [
+            (INITIAL_PLACEHOLDER, &self.initial),
+            ("{{INITIALS}}", &self.initials),
+            ("{{NAME}}", &self.name),
+            ("{{LOCATION}}", &self.location),
+            ("{{COLOR}}", &self.color),
+            ("{{ACCENT}}", &self.accent),
+        ]
+    }
+    /// Identifies the filled in template if only the initial changes it.
+    ///
+    /// None for templates such as `initials` that use the name or its color, as those are unique to each project
+    fn shared_key(&self, template: &str) -> Option<String> {
+        let only_initial = self.placeholders().iter().all(|(placeholder, _)| {
+            *placeholder == INITIAL_PLACEHOLDER || !template.contains(placeholder)
+        });
+        only_initial.then(|| format!("{}-{}", to_hex(&Sha256::digest(template)), self.initial))
+    }
+    /// Replaces every placeholder in the template
+    fn fill(&self, template: &str) -> String {
+        let mut svg = template.to_string();
+        for (placeholder, value) in self.placeholders() {
+            svg = svg.replace(placeholder, value);
+        }
+        svg
+    }
+}
+/// The first grapheme of the word that is not punctuation, in uppercase.
+///
+/// Letters such as `ß` that uppercase to several letters are kept as is.
+/// Georgian is not uppercased because, like in titlecase, words start with the normal letter
+fn initial(word: &str) -> String {
+    let Some(grapheme) = word.graphemes(true).find(|grapheme| {
+        !grapheme
+            .chars()
+            .all(|c| c.is_whitespace() || c.is_ascii_punctuation())
+    }) else {
+        return "?".to_string();
+    };
+    let is_georgian = grapheme
+        .chars()
+        .next()
+        .is_some_and(|c| ('\u{10D0}'..='\u{10FF}').contains(&c));
+    let uppercase = grapheme.to_uppercase();
+    if is_georgian || uppercase.chars().count() > grapheme.chars().count() {
+        grapheme.to_string()
+    } else {
+        uppercase
+    }
+}
+/// Splits a name on `-`, `_`, `.`, spaces and camelCase humps
+fn split_words(name: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    for part in name.split(['-', '_', '.', ' ']) {
+        let mut start = 0;
+        let mut previous_lowercase = false;
+        for (index, c) in part.char_indices() {
+            if c.is_uppercase() && previous_lowercase {
+                words.push(&part[start..index]);
+                start = index;
+            }
+            previous_lowercase = c.is_lowercase();
+        }
+        if start < part.len() {
+            words.push(&part[start..]);
+        }
+    }
+    words
+}
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+/// Converts a hue in degrees, saturation and lightness to `#rrggbb`
+fn hsl_to_hex(hue: f32, saturation: f32, lightness: f32) -> String {
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let x = chroma * (1.0 - ((hue / 60.0) % 2.0 - 1.0).abs());
+    let (r, g, b) = match hue as u32 / 60 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = lightness - chroma / 2.0;
+    let channel = |value: f32| ((value + m) * 255.0).round() as u8;
+    format!("#{:02x}{:02x}{:02x}", channel(r), channel(g), channel(b))
 }
 
 mod ico {
-    use anyhow::{Context, Result};
+    use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+    use anyhow::{anyhow, Context, Result};
     use once_cell::sync::Lazy;
     use tiny_skia::{IntSize, Pixmap};
-    use usvg::{fontdb::Database, TreeParsing, TreeTextToPath};
+    use usvg::{fontdb::Database, TreeParsing, TreeTextToPath, TreeWriting};
 
-    use super::Fonts;
+    use super::{Fonts, TemplateVariables};
+    /// Loads the bundled fonts. Fira Sans is used by the templates and DejaVu Sans covers the glyphs it is missing
     pub(super) fn load_fonts() -> Database {
         let mut fontdb = Database::new();
         for file in Fonts::iter() {
@@ -76,21 +684,184 @@ mod ico {
 
         fontdb
     }
-    static FONTDB: Lazy<Database> = once_cell::sync::Lazy::new(|| load_fonts());
-    /// Convert SVG to ICO
-    pub(super) fn svg_to_ico(svg: String) -> Result<Vec<u8>> {
-        let opt = usvg::Options {
-            dpi: 150f32,
-            ..Default::default()
-        };
+    static BUNDLED_FONTDB: Lazy<Database> = Lazy::new(load_fonts);
+    /// The bundled fonts followed by the system fonts.
+    ///
+    /// usvg falls back to the first font containing a glyph, so system fonts are only used for glyphs the bundled fonts do not have.
+    /// Loading them is slow, so this is only done if `system_fonts` is enabled
+    static SYSTEM_FONTDB: Lazy<Database> = Lazy::new(|| {
+        let mut fontdb = load_fonts();
+        fontdb.load_system_fonts();
+        fontdb
+    });
+    /// The fonts text in SVGs is rendered with
+    pub(super) fn fonts(system_fonts: bool) -> &'static Database {
+        if system_fonts {
+            &SYSTEM_FONTDB
+        } else {
+            &BUNDLED_FONTDB
+        }
+    }
+    /// The most SVGs kept by each thread. Only reached by names in many scripts
+    const MAX_PARSED_SVGS: usize = 256;
+    thread_local! {
+        /// SVGs parsed on this thread by [TemplateVariables::shared_key] and `system_fonts`.
+        /// usvg trees can not be sent between threads
+        static PARSED_SVGS: RefCell<HashMap<(String, bool), Rc<Image>>> = RefCell::new(HashMap::new());
+    }
+    /// An icon that can be rendered at any size
+    pub(super) enum Image {
+        /// An SVG with the text already converted to paths
+        Svg(usvg::Tree),
+        Raster(Pixmap),
+    }
+    impl Image {
+        pub(super) fn from_svg(svg: &str, system_fonts: bool) -> Result<Self> {
+            let opt = usvg::Options {
+                dpi: 150f32,
+                ..Default::default()
+            };
+
+            let mut svg = usvg::Tree::from_str(svg, &opt)?;
 
-        let mut svg = usvg::Tree::from_str(&svg, &opt)?;
+            svg.convert_text(fonts(system_fonts));
+            Ok(Self::Svg(svg))
+        }
+        /// Fills in the template and parses it.
+        ///
+        /// Reuses the tree if this thread parsed the same template with the same initial before
+        pub(super) fn from_template(
+            template: &str,
+            variables: &TemplateVariables,
+            system_fonts: bool,
+        ) -> Result<Rc<Self>> {
+            let Some(key) = variables.shared_key(template) else {
+                return Ok(Rc::new(Self::from_svg(
+                    &variables.fill(template),
+                    system_fonts,
+                )?));
+            };
+            let key = (key, system_fonts);
+            if let Some(image) = PARSED_SVGS.with(|parsed| parsed.borrow().get(&key).cloned()) {
+                return Ok(image);
+            }
+            let image = Rc::new(Self::from_svg(&variables.fill(template), system_fonts)?);
+            PARSED_SVGS.with(|parsed| {
+                let mut parsed = parsed.borrow_mut();
+                if parsed.len() >= MAX_PARSED_SVGS {
+                    parsed.clear();
+                }
+                parsed.insert(key, image.clone());
+            });
+            Ok(image)
+        }
+        pub(super) fn from_png(png: &[u8]) -> Result<Self> {
+            let pixmap = Pixmap::decode_png(png).context("Unable to decode PNG")?;
+            Ok(Self::Raster(pixmap))
+        }
+        /// Uses the largest image in the ICO
+        pub(super) fn from_ico(data: &[u8]) -> Result<Self> {
+            let icon_dir = ico::IconDir::read(std::io::Cursor::new(data))?;
+            let entry = icon_dir
+                .entries()
+                .iter()
+                .max_by_key(|entry| entry.width() * entry.height())
+                .context("ICO does not contain any images")?;
+            let image = entry.decode()?;
+            let size = IntSize::from_wh(image.width(), image.height()).context("Empty ICO")?;
+            let mut rgba = image.rgba_data().to_vec();
+            // tiny-skia uses premultiplied alpha
+            for pixel in rgba.chunks_exact_mut(4) {
+                let alpha = pixel[3] as u16;
+                for channel in &mut pixel[..3] {
+                    *channel = ((*channel as u16 * alpha + 127) / 255) as u8;
+                }
+            }
+            let pixmap = Pixmap::from_vec(rgba, size).context("Invalid ICO image")?;
+            Ok(Self::Raster(pixmap))
+        }
+        /// Renders a square image. Raster images are scaled to fit
+        pub(super) fn render(&self, size: u32) -> Result<Pixmap> {
+            match self {
+                Self::Svg(svg) => rasterize(svg, size),
+                Self::Raster(pixmap) => fit(pixmap, size, size),
+            }
+        }
+        /// The SVG source with text converted to paths. None for raster images
+        pub(super) fn to_svg(&self) -> Option<String> {
+            match self {
+                Self::Svg(svg) => Some(svg.to_string(&usvg::XmlOptions::default())),
+                Self::Raster(_) => None,
+            }
+        }
+        /// Creates an ICO containing an image for each size
+        pub(super) fn to_ico(&self, sizes: &[u32]) -> Result<Vec<u8>> {
+            if sizes.is_empty() {
+                return Err(anyhow!("icon_sizes must not be empty"));
+            }
+            if let Some(size) = sizes
+                .iter()
+                .find(|size| !(1..=MAX_ICO_SIZE).contains(*size))
+            {
+                return Err(anyhow!(
+                    "Icon size {} must be between 1 and {}",
+                    size,
+                    MAX_ICO_SIZE
+                ));
+            }
+            let images = sizes
+                .iter()
+                .map(|size| self.render(*size))
+                .collect::<Result<Vec<_>>>()?;
+            create_ico(images)
+        }
+    }
 
-        svg.convert_text(&FONTDB);
-        let result = rasterize(&svg, 256)?;
-        let ico = create_ico(result)?;
-        Ok(ico)
+    /// Checks that the template parses once the placeholders are filled in
+    pub(super) fn parse_template(template: &str) -> Result<()> {
+        let sample = super::IconRequest {
+            template: String::new(),
+            name: "auto_project".to_string(),
+            location: Some("Projects".to_string()),
+        };
+        let svg = super::TemplateVariables::new(&sample, "auto_project").fill(template);
+        usvg::Tree::from_str(&svg, &usvg::Options::default())?;
+        Ok(())
     }
+    /// Convert PNG to ICO. Images larger than 256x256 are scaled down
+    pub(super) fn png_to_ico(png: &[u8]) -> Result<Vec<u8>> {
+        let pixmap = Pixmap::decode_png(png).context("Unable to decode PNG")?;
+        let largest_side = pixmap.width().max(pixmap.height());
+        if largest_side <= MAX_ICO_SIZE {
+            return create_ico(vec![pixmap]);
+        }
+        let scale = MAX_ICO_SIZE as f32 / largest_side as f32;
+        let width = ((pixmap.width() as f32 * scale).round() as u32).max(1);
+        let height = ((pixmap.height() as f32 * scale).round() as u32).max(1);
+        create_ico(vec![fit(&pixmap, width, height)?])
+    }
+    /// Scales the image to fit inside of width by height and centers it
+    fn fit(pixmap: &Pixmap, width: u32, height: u32) -> Result<Pixmap> {
+        let scale =
+            (width as f32 / pixmap.width() as f32).min(height as f32 / pixmap.height() as f32);
+        let x = (width as f32 - pixmap.width() as f32 * scale) / 2.0;
+        let y = (height as f32 - pixmap.height() as f32 * scale) / 2.0;
+        let mut scaled = Pixmap::new(width, height).context("Unable to create pixmap")?;
+        scaled.draw_pixmap(
+            0,
+            0,
+            pixmap.as_ref(),
+            &tiny_skia::PixmapPaint {
+                quality: tiny_skia::FilterQuality::Bicubic,
+                ..Default::default()
+            },
+            tiny_skia::Transform::from_scale(scale, scale).post_translate(x, y),
+            None,
+        );
+        Ok(scaled)
+    }
+    /// The largest size Windows uses for icons
+    const MAX_ICO_SIZE: u32 = 256;
 
     fn rasterize(svg: &usvg::Tree, height_in_pixels: u32) -> Result<tiny_skia::Pixmap> {
         let _target_size = usvg::Size::from_wh(height_in_pixels as f32, height_in_pixels as f32)
@@ -112,10 +883,21 @@ mod ico {
             .context("Unable to create pixmap")
     }
 
-    fn create_ico(png: Pixmap) -> Result<Vec<u8>> {
+    fn create_ico(images: Vec<Pixmap>) -> Result<Vec<u8>> {
         let mut icon_dir = ico::IconDir::new(ico::ResourceType::Icon);
-        let image = ico::IconImage::from_rgba_data(png.width(), png.height(), png.take());
-        icon_dir.add_entry(ico::IconDirEntry::encode(&image)?);
+        for pixmap in images {
+            // tiny-skia uses premultiplied alpha while ICO images use straight alpha
+            let rgba = pixmap
+                .pixels()
+                .iter()
+                .flat_map(|pixel| {
+                    let color = pixel.demultiply();
+                    [color.red(), color.green(), color.blue(), color.alpha()]
+                })
+                .collect();
+            let image = ico::IconImage::from_rgba_data(pixmap.width(), pixmap.height(), rgba);
+            icon_dir.add_entry(ico::IconDirEntry::encode(&image)?);
+        }
         let mut buf = Vec::new();
         icon_dir.write(&mut buf)?;
         Ok(buf)
@@ -124,12 +906,13 @@ mod ico {
 
 #[cfg(test)]
 mod tests {
-    use std::path::PathBuf;
+    use std::path::{Path, PathBuf};
 
-    use rust_embed::RustEmbed;
+    use clap::ValueEnum;
     use usvg::fontdb::{Family, Weight};
 
     use super::Icons;
+    use crate::config::IconStyle;
 
     #[test]
     fn load_fonts() {
@@ -153,6 +936,294 @@ mod tests {
         assert_eq!(font.post_script_name, "FiraSans-Black");
     }
     #[test]
+    fn resolve_style() {
+        let temp = tempfile::tempdir().unwrap();
+        let project = |name: &str, files: &[&str]| {
+            let dir = temp.path().join(name);
+            std::fs::create_dir(&dir).unwrap();
+            for file in files {
+                std::fs::write(dir.join(file), "").unwrap();
+            }
+            IconStyle::Auto.resolve(&dir)
+        };
+        assert_eq!(project("rust", &["Cargo.toml"]), IconStyle::Cargo);
+        assert_eq!(
+            project("web", &["package.json", "tsconfig.json"]),
+            IconStyle::TypeScript
+        );
+        assert_eq!(project("node", &["package.json"]), IconStyle::JavaScript);
+        assert_eq!(project("python", &["pyproject.toml"]), IconStyle::Python);
+        assert_eq!(project("go", &["go.mod"]), IconStyle::Go);
+        assert_eq!(project("java", &["build.gradle.kts"]), IconStyle::Java);
+        assert_eq!(project("unknown", &["Makefile"]), IconStyle::Default);
+        assert_eq!(
+            IconStyle::Go.resolve(&temp.path().join("rust")),
+            IconStyle::Go
+        );
+    }
+    #[test]
+    fn user_templates() {
+        let templates = tempfile::tempdir().unwrap();
+        let embedded = super::load_template("cargo", templates.path()).unwrap();
+        assert!(embedded.contains("{{INITIAL}}"));
+
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 8 8"><text>{{INITIAL}}</text></svg>"#;
+        std::fs::write(templates.path().join("cargo.svg"), svg).unwrap();
+        std::fs::write(templates.path().join("brand.svg"), svg).unwrap();
+        std::fs::write(templates.path().join("broken.svg"), "<svg").unwrap();
+        let overridden = super::load_template("cargo", templates.path()).unwrap();
+        assert_eq!(overridden, svg);
+        assert!(super::load_template("brand", templates.path()).is_ok());
+
+        assert!(super::load_template("broken", templates.path()).is_err());
+        assert!(super::load_template("missing", templates.path()).is_err());
+        assert!(super::load_template("../brand", templates.path()).is_err());
+    }
+    #[test]
+    fn initial() {
+        assert_eq!(super::initial("äpfel"), "Ä");
+        assert_eq!(super::initial("łódź"), "Ł");
+        assert_eq!(super::initial("жук"), "Ж");
+        assert_eq!(super::initial("e\u{301}cole"), "E\u{301}");
+        assert_eq!(super::initial("🚀rocket"), "🚀");
+        assert_eq!(super::initial("ßtraße"), "ß");
+        assert_eq!(super::initial("ანა"), "ა");
+        assert_eq!(super::initial(".dotfiles"), "D");
+        assert_eq!(super::initial(""), "?");
+    }
+    #[test]
+    fn bundled_font_coverage() {
+        let fonts = super::ico::load_fonts();
+        for c in ['Ä', 'Ł', 'Ж', 'Ω', 'ა', 'Ա', 'א'] {
+            let covered = fonts.faces().any(|face| {
+                fonts
+                    .with_face_data(face.id, |data, index| {
+                        let face = ttf_parser::Face::parse(data, index).unwrap();
+                        face.glyph_index(c).is_some()
+                    })
+                    .unwrap_or(false)
+            });
+            assert!(covered, "No bundled font contains {c}");
+        }
+    }
+    #[test]
+    fn template_variables() {
+        assert_eq!(super::split_words("auto_project"), vec!["auto", "project"]);
+        assert_eq!(
+            super::split_words("myHTTPServer-v2"),
+            vec!["my", "HTTPServer", "v2"]
+        );
+        let request = super::IconRequest {
+            template: "default".to_string(),
+            name: "auto_project".to_string(),
+            location: Some("Code & Stuff".to_string()),
+        };
+        let variables = super::TemplateVariables::new(&request, "auto_project - Code");
+        assert_eq!(
+            variables.fill("{{INITIAL}} {{INITIALS}} {{NAME}} {{LOCATION}}"),
+            "A AP auto_project Code &amp; Stuff"
+        );
+        assert!(variables.color.starts_with('#') && variables.color.len() == 7);
+        assert_eq!(
+            variables,
+            super::TemplateVariables::new(&request, "auto_project - Code")
+        );
+        let other = super::TemplateVariables::new(&request, "auto_project - Work");
+        assert_ne!(variables.color, other.color);
+        let key = super::render_key("<svg/>", &variables, &[16], false);
+        assert_eq!(key, super::render_key("<svg/>", &variables, &[16], false));
+        assert_ne!(key, super::render_key("<svg/>", &other, &[16], false));
+        assert_ne!(
+            key,
+            super::render_key("<svg></svg>", &variables, &[16], false)
+        );
+        assert_ne!(
+            key,
+            super::render_key("<svg/>", &variables, &[16, 32], false)
+        );
+        assert_ne!(key, super::render_key("<svg/>", &variables, &[16], true));
+        let initial_only = "<text>{{INITIAL}}</text>";
+        assert!(variables.shared_key(initial_only).is_some());
+        assert_eq!(
+            variables.shared_key(initial_only),
+            other.shared_key(initial_only)
+        );
+        assert_ne!(
+            variables.shared_key(initial_only),
+            variables.shared_key("<svg>{{INITIAL}}</svg>")
+        );
+        assert!(variables
+            .shared_key("<text fill=\"{{COLOR}}\">{{INITIAL}}</text>")
+            .is_none());
+        assert_eq!(super::hsl_to_hex(0.0, 1.0, 0.5), "#ff0000");
+        assert_eq!(super::hsl_to_hex(240.0, 1.0, 0.5), "#0000ff");
+    }
+    #[test]
+    fn icon_sizes() {
+        let svg = String::from_utf8(Icons::get("cargo.svg").unwrap().data.to_vec()).unwrap();
+        let sizes = [16, 24, 32, 48, 64, 128, 256];
+        let image = super::ico::Image::from_svg(&svg, false).unwrap();
+        let ico = image.to_ico(&sizes).unwrap();
+        let icon_dir = ico::IconDir::read(std::io::Cursor::new(ico)).unwrap();
+        let entries: Vec<_> = icon_dir
+            .entries()
+            .iter()
+            .map(|entry| (entry.width(), entry.height(), entry.bits_per_pixel()))
+            .collect();
+        let expected: Vec<_> = sizes.iter().map(|size| (*size, *size, 32)).collect();
+        assert_eq!(entries, expected);
+        for entry in icon_dir.entries() {
+            let image = entry.decode().unwrap();
+            assert!(image.rgba_data().iter().any(|byte| *byte != 0));
+        }
+
+        assert!(image.to_ico(&[]).is_err());
+        assert!(image.to_ico(&[512]).is_err());
+    }
+    #[test]
+    fn theme_icons() {
+        let theme = tempfile::tempdir().unwrap();
+        let svg = String::from_utf8(Icons::get("go.svg").unwrap().data.to_vec()).unwrap();
+        let image = super::ico::Image::from_svg(&svg, false).unwrap();
+        super::install_theme_icons(&image, "auto_project-go", &[16, 48], theme.path()).unwrap();
+        for size in [16, 48] {
+            let png = theme
+                .path()
+                .join(format!("{size}x{size}/apps/auto_project-go.png"));
+            let pixmap = tiny_skia::Pixmap::load_png(png).unwrap();
+            assert_eq!((pixmap.width(), pixmap.height()), (size, size));
+        }
+        let scalable = theme.path().join("scalable/apps/auto_project-go.svg");
+        let scalable = std::fs::read_to_string(scalable).unwrap();
+        assert!(!scalable.contains("<text"));
+
+        let ico = super::ico::png_to_ico(
+            &tiny_skia::Pixmap::new(64, 32)
+                .unwrap()
+                .encode_png()
+                .unwrap(),
+        )
+        .unwrap();
+        let image = super::ico::Image::from_ico(&ico).unwrap();
+        assert_eq!(image.render(24).unwrap().width(), 24);
+        assert!(image.to_svg().is_none());
+
+        assert_ne!(super::theme_icon_name("a b"), super::theme_icon_name("a_b"));
+        assert!(super::theme_icon_name("a b").starts_with("auto_project-a_b-"));
+    }
+    #[test]
+    fn build_icns() {
+        let svg = String::from_utf8(Icons::get("python.svg").unwrap().data.to_vec()).unwrap();
+        let image = super::ico::Image::from_svg(&svg, false).unwrap();
+        let bytes = super::build_icns(&image).unwrap();
+        let family = icns::IconFamily::read(std::io::Cursor::new(bytes)).unwrap();
+        let mut sizes: Vec<_> = family
+            .available_icons()
+            .iter()
+            .map(|icon_type| (icon_type.pixel_width(), icon_type.pixel_density()))
+            .collect();
+        sizes.sort();
+        assert_eq!(
+            sizes,
+            vec![
+                (16, 1),
+                (32, 1),
+                (32, 2),
+                (64, 2),
+                (128, 1),
+                (256, 1),
+                (256, 2),
+                (512, 1),
+                (512, 2),
+                (1024, 2)
+            ]
+        );
+        let largest = family
+            .get_icon_with_type(icns::IconType::RGBA32_512x512_2x)
+            .unwrap();
+        assert_eq!(largest.width(), 1024);
+        assert!(largest.data().chunks_exact(4).any(|pixel| pixel[3] == 255));
+    }
+    #[test]
+    fn unused_files() {
+        let dir = tempfile::tempdir().unwrap();
+        for file in ["used.ico", "unused.ico", "shortcuts.toml"] {
+            std::fs::write(dir.path().join(file), "").unwrap();
+        }
+        std::fs::create_dir(dir.path().join("nested.ico")).unwrap();
+        let used = dir.path().join("used.ico");
+        let unused = super::unused_files(dir.path(), |path| {
+            path.extension().is_some_and(|extension| extension == "ico") && path != used
+        })
+        .unwrap();
+        assert_eq!(unused, vec![dir.path().join("unused.ico")]);
+        assert!(super::unused_files(&dir.path().join("missing"), |_| true)
+            .unwrap()
+            .is_empty());
+
+        let project = |name: &str| crate::config::Project {
+            path: PathBuf::from(format!("/code/{name}")),
+            name: name.to_string(),
+            icon: Some(PathBuf::from(format!("/data/{name}.ico"))),
+            ..Default::default()
+        };
+        let discovered = vec![crate::discovery::DiscoveredProject {
+            project: project("enabled"),
+            location: None,
+            render_icon: None,
+        }];
+        // The disabled project is only in the config
+        let added = vec![project("enabled"), project("disabled")];
+        let kept = super::kept_projects(&discovered, &added);
+        assert!(kept.contains(&("disabled", Some(Path::new("/data/disabled.ico")))));
+        assert!(kept.contains(&("enabled", Some(Path::new("/data/enabled.ico")))));
+
+        let cache = tempfile::tempdir().unwrap();
+        for file in ["A-Default.svg", "B-Cargo.ico", "notes.txt"] {
+            std::fs::write(cache.path().join(file), "").unwrap();
+        }
+        std::fs::create_dir(cache.path().join("icons")).unwrap();
+        std::fs::write(cache.path().join("icons/current.ico"), "").unwrap();
+        let mut legacy = super::legacy_cache_files(cache.path()).unwrap();
+        legacy.sort();
+        assert_eq!(
+            legacy,
+            vec![
+                cache.path().join("A-Default.svg"),
+                cache.path().join("B-Cargo.ico")
+            ]
+        );
+    }
+    #[test]
+    fn png_to_ico() {
+        let png = tiny_skia::Pixmap::new(512, 300)
+            .unwrap()
+            .encode_png()
+            .unwrap();
+        let ico = super::ico::png_to_ico(&png).unwrap();
+        let icon_dir = ico::IconDir::read(std::io::Cursor::new(ico)).unwrap();
+        let entry = &icon_dir.entries()[0];
+        assert_eq!((entry.width(), entry.height()), (256, 150));
+
+        // Semi-transparent pixels keep their color
+        let mut pixmap = tiny_skia::Pixmap::new(4, 4).unwrap();
+        pixmap.fill(tiny_skia::Color::from_rgba8(200, 100, 50, 128));
+        let ico = super::ico::png_to_ico(&pixmap.encode_png().unwrap()).unwrap();
+        let icon_dir = ico::IconDir::read(std::io::Cursor::new(&ico)).unwrap();
+        let image = icon_dir.entries()[0].decode().unwrap();
+        let pixel = &image.rgba_data()[..4];
+        for (channel, expected) in pixel.iter().zip([200u8, 100, 50, 128]) {
+            assert!(channel.abs_diff(expected) <= 1, "{pixel:?}");
+        }
+        // And survive a round trip through an imported ICO
+        let pixmap = super::ico::Image::from_ico(&ico)
+            .unwrap()
+            .render(4)
+            .unwrap();
+        let color = pixmap.pixels()[5].demultiply();
+        assert!(color.red().abs_diff(200) <= 2, "{color:?}");
+    }
+    #[test]
     fn test() {
         std::env::set_var("RUST_LOG", "TRACE");
         pretty_env_logger::init();
@@ -160,22 +1231,29 @@ mod tests {
         if !image_tests_directory.exists() {
             std::fs::create_dir_all(&image_tests_directory).unwrap();
         }
-        for ele in <Icons as RustEmbed>::iter() {
-            let name = ele.as_ref();
-            if !name.ends_with(".svg") {
-                continue;
-            }
-            let data = Icons::get(name).unwrap();
+        for style in IconStyle::value_variants() {
+            let name = style.template();
+            let data = Icons::get(&format!("{name}.svg")).expect("Missing template");
             let string = String::from_utf8(data.data.to_vec()).unwrap();
             // Iterator A-Z
             for letter in 'A'..='Z' {
-                let letter = letter as char;
-                let svg = string.replace(r#"{{INITIAL}}"#, letter.to_string().as_str());
-                let path = image_tests_directory.join(format!("{}-{}.ico", letter, name));
+                let request = super::IconRequest {
+                    template: name.to_string(),
+                    name: format!("{letter}x_project"),
+                    location: None,
+                };
+                let svg = super::TemplateVariables::new(&request, &request.name).fill(&string);
+                let path = image_tests_directory.join(format!(
+                    "{}-{}.ico",
+                    letter,
+                    AsRef::<str>::as_ref(style)
+                ));
                 if path.exists() {
                     std::fs::remove_file(&path).unwrap();
                 }
-                let icon = super::ico::svg_to_ico(svg).expect("Unable to convert SVG to ICO");
+                let icon =
+                    super::ico::Image::from_svg(&svg, false).and_then(|image| image.to_ico(&[256]));
+                let icon = icon.expect("Unable to convert SVG to ICO");
                 std::fs::write(&path, icon).expect("Unable to write icon");
             }
         }
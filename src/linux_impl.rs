@@ -0,0 +1,215 @@
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context};
+use directories::BaseDirs;
+use log::debug;
+
+use crate::{
+    shortcuts::{InstalledShortcut, Shortcut, ShortcutBackend},
+    utils::unique_slug,
+};
+
+/// Prefix given to every desktop entry we create so they can be found again.
+const DESKTOP_FILE_PREFIX: &str = "auto_project-";
+
+/// Creates an XDG Desktop Entry for each project in `$XDG_DATA_HOME/applications`
+#[derive(Debug)]
+pub struct DesktopEntries {
+    applications: PathBuf,
+}
+impl DesktopEntries {
+    pub fn new(base: &BaseDirs) -> anyhow::Result<Self> {
+        let applications = base.data_dir().join("applications");
+        debug!("Putting desktop entries in {}", applications.display());
+        Ok(Self { applications })
+    }
+}
+impl ShortcutBackend for DesktopEntries {
+    fn shortcut_path(&self, shortcut: &Shortcut) -> PathBuf {
+        self.applications.join(desktop_file_name(&shortcut.name))
+    }
+
+    fn install(&mut self, shortcut: &Shortcut) -> anyhow::Result<InstalledShortcut> {
+        let entry = desktop_entry(shortcut)?;
+        if !self.applications.exists() {
+            std::fs::create_dir_all(&self.applications)
+                .context("Unable to create the applications directory")?;
+        }
+        let path = self.shortcut_path(shortcut);
+        std::fs::write(&path, entry)
+            .context(format!("Unable to write desktop entry {}", path.display()))?;
+        Ok(InstalledShortcut {
+            name: shortcut.name.clone(),
+            path,
+        })
+    }
+
+    fn remove(&mut self, shortcut: &InstalledShortcut) -> anyhow::Result<()> {
+        std::fs::remove_file(&shortcut.path).context(format!(
+            "Unable to remove desktop entry {}",
+            shortcut.path.display()
+        ))
+    }
+
+    fn list_installed(&self) -> anyhow::Result<Vec<InstalledShortcut>> {
+        let mut installed = Vec::new();
+        if !self.applications.exists() {
+            return Ok(installed);
+        }
+        for entry in self.applications.read_dir()? {
+            let entry = entry?;
+            let file_name = entry.file_name();
+            let file_name = file_name.to_string_lossy();
+            if let Some(name) = file_name
+                .strip_prefix(DESKTOP_FILE_PREFIX)
+                .and_then(|name| name.strip_suffix(".desktop"))
+            {
+                installed.push(InstalledShortcut {
+                    name: name.to_string(),
+                    path: entry.path(),
+                });
+            }
+        }
+        Ok(installed)
+    }
+}
+/// Builds the file name for a project's desktop entry.
+///
+/// Desktop file IDs may only contain ASCII letters, digits, `-`, `_` and `.`
+fn desktop_file_name(name: &str) -> String {
+    format!("{DESKTOP_FILE_PREFIX}{}.desktop", unique_slug(name))
+}
+
+fn desktop_entry(shortcut: &Shortcut) -> anyhow::Result<String> {
+    let mut exec = quote_exec_arg(path_to_str(&shortcut.target)?);
+    for argument in &shortcut.arguments {
+        exec.push(' ');
+        exec.push_str(&quote_exec_arg(argument));
+    }
+
+    let mut entry = String::from("[Desktop Entry]\n");
+    writeln!(entry, "Type=Application")?;
+    writeln!(entry, "Version=1.5")?;
+    writeln!(entry, "Name={}", escape_value(&shortcut.name))?;
+    writeln!(entry, "Comment={}", escape_value(&shortcut.description))?;
+    writeln!(entry, "Exec={}", escape_value(&exec))?;
+    writeln!(
+        entry,
+        "Path={}",
+        escape_value(path_to_str(&shortcut.working_directory)?)
+    )?;
+    if let Some(icon_name) = &shortcut.icon_name {
+        writeln!(entry, "Icon={}", escape_value(icon_name))?;
+    } else if let Some(icon) = &shortcut.icon {
+        writeln!(entry, "Icon={}", escape_value(path_to_str(icon)?))?;
+    }
+    writeln!(entry, "Terminal=false")?;
+    writeln!(entry, "Categories=Development;")?;
+    Ok(entry)
+}
+
+/// Desktop entries are UTF-8. So the path must be UTF-8 as well
+fn path_to_str(path: &Path) -> anyhow::Result<&str> {
+    path.to_str()
+        .ok_or_else(|| anyhow!("Path is not UTF-8, {:?}", path))
+}
+/// Escapes a value as described by the "Possible value types" section of the Desktop Entry Specification
+fn escape_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+/// Quotes an argument for the `Exec` key.
+///
+/// Field codes are not used so every `%` is escaped
+fn quote_exec_arg(arg: &str) -> String {
+    let mut quoted = String::with_capacity(arg.len() + 2);
+    quoted.push('"');
+    for c in arg.chars() {
+        match c {
+            '"' | '`' | '$' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '%' => quoted.push_str("%%"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::shortcuts::Shortcut;
+
+    #[test]
+    fn desktop_entry() {
+        let shortcut = Shortcut {
+            name: "my project - Code".to_string(),
+            target: PathBuf::from("/usr/bin/code"),
+            arguments: vec!["/home/user/code/my project".to_string()],
+            working_directory: PathBuf::from("/home/user/code/my project"),
+            description: "A 100% real project".to_string(),
+            icon: Some(PathBuf::from(
+                "/home/user/.local/share/auto_project/my project.ico",
+            )),
+            icon_name: None,
+        };
+        let entry = super::desktop_entry(&shortcut).unwrap();
+        assert_eq!(
+            entry,
+            "[Desktop Entry]
+Type=Application
+Version=1.5
+Name=my project - Code
+Comment=A 100% real project
+Exec=\"/usr/bin/code\" \"/home/user/code/my project\"
+Path=/home/user/code/my project
+Icon=/home/user/.local/share/auto_project/my project.ico
+Terminal=false
+Categories=Development;
+"
+        );
+        let themed = Shortcut {
+            icon_name: Some("auto_project-my_project_-_Code-0a1b2c3d".to_string()),
+            ..shortcut
+        };
+        let entry = super::desktop_entry(&themed).unwrap();
+        assert!(entry.contains("\nIcon=auto_project-my_project_-_Code-0a1b2c3d\n"));
+    }
+    #[test]
+    fn quote_exec_arg() {
+        assert_eq!(super::quote_exec_arg("/tmp/a b"), r#""/tmp/a b""#);
+        assert_eq!(super::quote_exec_arg("100%"), r#""100%%""#);
+        assert_eq!(super::quote_exec_arg(r#"$a"b"#), r#""\$a\"b""#);
+        // Backslashes are escaped twice. Once for the Exec key and once for the value
+        assert_eq!(
+            super::escape_value(&super::quote_exec_arg(r"a\b")),
+            r#""a\\\\b""#
+        );
+    }
+    #[test]
+    fn desktop_file_name() {
+        let name = super::desktop_file_name("my project - Code");
+        assert!(name.starts_with("auto_project-my_project_-_Code-"));
+        assert!(name.ends_with(".desktop"));
+        assert_ne!(
+            super::desktop_file_name("a b - Code"),
+            super::desktop_file_name("a_b - Code")
+        );
+    }
+}
@@ -0,0 +1,557 @@
+//! Writes Windows Shell Link (.lnk) files as described by
+//! [MS-SHLLINK](https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-shllink/16cb4ca1-9339-4d0c-a68d-bf1d6cc0f943)
+//!
+//! Unlike the COM based [IShellLinkW](https://learn.microsoft.com/en-us/windows/win32/api/shobjidl_core/nn-shobjidl_core-ishelllinkw)
+//! this works on every platform. The target is stored inside of a LinkInfo structure instead of an IDList.
+use std::{
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::shortcuts::{InstalledShortcut, Shortcut, ShortcutBackend};
+
+/// 00021401-0000-0000-C000-000000000046
+const LINK_CLSID: [u8; 16] = [
+    0x01, 0x14, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0xC0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46,
+];
+const HEADER_SIZE: u32 = 0x4C;
+/// LinkInfo header size when the optional Unicode offsets are present
+const LINK_INFO_HEADER_SIZE: u32 = 0x24;
+const VOLUME_ID_SIZE: u32 = 0x10;
+const DRIVE_FIXED: u32 = 3;
+
+mod link_flags {
+    pub const HAS_LINK_INFO: u32 = 0x0000_0002;
+    pub const HAS_NAME: u32 = 0x0000_0004;
+    pub const HAS_WORKING_DIR: u32 = 0x0000_0010;
+    pub const HAS_ARGUMENTS: u32 = 0x0000_0020;
+    pub const HAS_ICON_LOCATION: u32 = 0x0000_0040;
+    pub const IS_UNICODE: u32 = 0x0000_0080;
+}
+/// The window state of the launched application
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[repr(u32)]
+pub enum ShowCommand {
+    #[default]
+    Normal = 1,
+    Maximized = 3,
+    MinimizedNoActive = 7,
+}
+
+/// A Shell Link. Strings are written as UTF-16
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ShellLink {
+    /// Absolute path of the file the link points to
+    pub target: String,
+    pub arguments: Option<String>,
+    pub working_directory: Option<String>,
+    /// Stored as the NAME_STRING. Shown as the tooltip
+    pub description: Option<String>,
+    pub icon_location: Option<String>,
+    pub icon_index: i32,
+    pub show_command: ShowCommand,
+}
+impl From<&Shortcut> for ShellLink {
+    fn from(shortcut: &Shortcut) -> Self {
+        Self {
+            target: shortcut.target.to_string_lossy().into_owned(),
+            arguments: Some(join_arguments(&shortcut.arguments)).filter(|a| !a.is_empty()),
+            working_directory: Some(shortcut.working_directory.to_string_lossy().into_owned()),
+            description: Some(shortcut.description.clone()),
+            icon_location: shortcut
+                .icon
+                .as_ref()
+                .map(|icon| icon.to_string_lossy().into_owned()),
+            icon_index: 0,
+            show_command: ShowCommand::Normal,
+        }
+    }
+}
+impl ShellLink {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.write_to(&mut bytes)
+            .expect("Writing to a Vec can not fail");
+        bytes
+    }
+
+    pub fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+        let string_data = [
+            (link_flags::HAS_NAME, &self.description),
+            (link_flags::HAS_WORKING_DIR, &self.working_directory),
+            (link_flags::HAS_ARGUMENTS, &self.arguments),
+            (link_flags::HAS_ICON_LOCATION, &self.icon_location),
+        ];
+        let mut flags = link_flags::HAS_LINK_INFO | link_flags::IS_UNICODE;
+        for (flag, value) in &string_data {
+            if value.is_some() {
+                flags |= flag;
+            }
+        }
+        // ShellLinkHeader
+        writer.write_all(&HEADER_SIZE.to_le_bytes())?;
+        writer.write_all(&LINK_CLSID)?;
+        writer.write_all(&flags.to_le_bytes())?;
+        // FileAttributes
+        writer.write_all(&0u32.to_le_bytes())?;
+        // CreationTime, AccessTime and WriteTime. Left empty so the output is reproducible
+        writer.write_all(&[0; 24])?;
+        // FileSize
+        writer.write_all(&0u32.to_le_bytes())?;
+        writer.write_all(&self.icon_index.to_le_bytes())?;
+        writer.write_all(&(self.show_command as u32).to_le_bytes())?;
+        // HotKey, Reserved1, Reserved2 and Reserved3
+        writer.write_all(&[0; 12])?;
+
+        writer.write_all(&self.link_info())?;
+
+        for (_, value) in string_data {
+            if let Some(value) = value {
+                write_string_data(&mut writer, value)?;
+            }
+        }
+        // TerminalBlock of the ExtraData
+        writer.write_all(&0u32.to_le_bytes())?;
+        Ok(())
+    }
+    /// A LinkInfo with a VolumeID and LocalBasePath.
+    fn link_info(&self) -> Vec<u8> {
+        let ansi_path = to_ansi(&self.target);
+        let unicode_path = to_utf16_null_terminated(&self.target);
+
+        let volume_id_offset = LINK_INFO_HEADER_SIZE;
+        // The VolumeID is followed by an empty volume label
+        let local_base_path_offset = volume_id_offset + VOLUME_ID_SIZE + 1;
+        let common_path_suffix_offset = local_base_path_offset + ansi_path.len() as u32;
+        // An empty CommonPathSuffix
+        let local_base_path_offset_unicode = common_path_suffix_offset + 1;
+        let common_path_suffix_offset_unicode =
+            local_base_path_offset_unicode + unicode_path.len() as u32;
+        let link_info_size = common_path_suffix_offset_unicode + 2;
+
+        let mut link_info = Vec::with_capacity(link_info_size as usize);
+        link_info.extend_from_slice(&link_info_size.to_le_bytes());
+        link_info.extend_from_slice(&LINK_INFO_HEADER_SIZE.to_le_bytes());
+        // VolumeIDAndLocalBasePath
+        link_info.extend_from_slice(&1u32.to_le_bytes());
+        link_info.extend_from_slice(&volume_id_offset.to_le_bytes());
+        link_info.extend_from_slice(&local_base_path_offset.to_le_bytes());
+        // CommonNetworkRelativeLinkOffset
+        link_info.extend_from_slice(&0u32.to_le_bytes());
+        link_info.extend_from_slice(&common_path_suffix_offset.to_le_bytes());
+        link_info.extend_from_slice(&local_base_path_offset_unicode.to_le_bytes());
+        link_info.extend_from_slice(&common_path_suffix_offset_unicode.to_le_bytes());
+        // VolumeID
+        link_info.extend_from_slice(&(VOLUME_ID_SIZE + 1).to_le_bytes());
+        link_info.extend_from_slice(&DRIVE_FIXED.to_le_bytes());
+        // DriveSerialNumber
+        link_info.extend_from_slice(&0u32.to_le_bytes());
+        // VolumeLabelOffset
+        link_info.extend_from_slice(&VOLUME_ID_SIZE.to_le_bytes());
+        link_info.push(0);
+
+        link_info.extend_from_slice(&ansi_path);
+        link_info.push(0);
+        link_info.extend_from_slice(&unicode_path);
+        link_info.extend_from_slice(&[0, 0]);
+        debug_assert_eq!(link_info.len(), link_info_size as usize);
+        link_info
+    }
+}
+/// Joins the arguments into a single command line.
+///
+/// Quotes the same way `CommandLineToArgvW` parses. Backslashes are only escaped in front of a quote
+pub fn join_arguments(arguments: &[String]) -> String {
+    arguments
+        .iter()
+        .map(|argument| quote_argument(argument))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+fn quote_argument(argument: &str) -> String {
+    if !argument.is_empty() && !argument.contains([' ', '\t', '\n', '\x0b', '"']) {
+        return argument.to_string();
+    }
+    let mut quoted = String::with_capacity(argument.len() + 2);
+    quoted.push('"');
+    let mut backslashes = 0;
+    for character in argument.chars() {
+        match character {
+            '\\' => backslashes += 1,
+            '"' => {
+                quoted.extend(std::iter::repeat_n('\\', backslashes * 2 + 1));
+                quoted.push('"');
+                backslashes = 0;
+            }
+            _ => {
+                quoted.extend(std::iter::repeat_n('\\', backslashes));
+                quoted.push(character);
+                backslashes = 0;
+            }
+        }
+    }
+    // The closing quote must not be escaped
+    quoted.extend(std::iter::repeat_n('\\', backslashes * 2));
+    quoted.push('"');
+    quoted
+}
+/// Writes a StringData structure. The length is in characters not bytes
+fn write_string_data(writer: &mut impl Write, value: &str) -> io::Result<()> {
+    let characters: Vec<u16> = value.encode_utf16().collect();
+    let count = u16::try_from(characters.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "String is too long"))?;
+    writer.write_all(&count.to_le_bytes())?;
+    for character in characters {
+        writer.write_all(&character.to_le_bytes())?;
+    }
+    Ok(())
+}
+/// Null terminated system code page string. Characters outside of ASCII are replaced with `?`
+///
+/// Windows will use the Unicode version of the path
+fn to_ansi(value: &str) -> Vec<u8> {
+    let mut bytes: Vec<u8> = value
+        .chars()
+        .map(|c| if c.is_ascii() { c as u8 } else { b'?' })
+        .collect();
+    bytes.push(0);
+    bytes
+}
+fn to_utf16_null_terminated(value: &str) -> Vec<u8> {
+    value
+        .encode_utf16()
+        .chain(std::iter::once(0))
+        .flat_map(u16::to_le_bytes)
+        .collect()
+}
+/// The .lnk file of the shortcut in the directory
+pub fn lnk_path(directory: &Path, shortcut: &Shortcut) -> PathBuf {
+    directory.join(format!("{}.lnk", shortcut.name))
+}
+/// The .lnk files in the directory. Named after the file without the extension
+pub fn list_lnk_files(directory: &Path) -> anyhow::Result<Vec<InstalledShortcut>> {
+    let mut installed = Vec::new();
+    if !directory.exists() {
+        return Ok(installed);
+    }
+    for entry in directory.read_dir()? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|extension| extension == "lnk") {
+            installed.push(InstalledShortcut {
+                name: path.file_stem().unwrap().to_string_lossy().into_owned(),
+                path,
+            });
+        }
+    }
+    Ok(installed)
+}
+/// Deletes an installed .lnk file
+pub fn remove_lnk(shortcut: &InstalledShortcut) -> anyhow::Result<()> {
+    std::fs::remove_file(&shortcut.path).context(format!(
+        "Unable to remove shortcut {}",
+        shortcut.path.display()
+    ))
+}
+/// Writes .lnk files into a directory.
+///
+/// Can be used to build shortcuts for a Windows machine from any platform
+#[derive(Debug)]
+pub struct LnkDirectory {
+    directory: PathBuf,
+    show_command: ShowCommand,
+}
+impl LnkDirectory {
+    pub fn new(directory: PathBuf, show_command: ShowCommand) -> anyhow::Result<Self> {
+        debug!("Putting shortcuts in {}", directory.display());
+        Ok(Self {
+            directory,
+            show_command,
+        })
+    }
+}
+impl ShortcutBackend for LnkDirectory {
+    fn shortcut_path(&self, shortcut: &Shortcut) -> PathBuf {
+        lnk_path(&self.directory, shortcut)
+    }
+
+    fn install(&mut self, shortcut: &Shortcut) -> anyhow::Result<InstalledShortcut> {
+        if !self.directory.exists() {
+            std::fs::create_dir_all(&self.directory).context(format!(
+                "Unable to create shortcut directory {}",
+                self.directory.display()
+            ))?;
+        }
+        let path = self.shortcut_path(shortcut);
+        let link = ShellLink {
+            show_command: self.show_command,
+            ..ShellLink::from(shortcut)
+        };
+        std::fs::write(&path, link.to_bytes())
+            .context(format!("Unable to write shortcut {}", path.display()))?;
+        Ok(InstalledShortcut {
+            name: shortcut.name.clone(),
+            path,
+        })
+    }
+
+    fn remove(&mut self, shortcut: &InstalledShortcut) -> anyhow::Result<()> {
+        remove_lnk(shortcut)
+    }
+
+    fn list_installed(&self) -> anyhow::Result<Vec<InstalledShortcut>> {
+        list_lnk_files(&self.directory)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{join_arguments, ShellLink, ShowCommand};
+
+    fn golden_file(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("test_data")
+            .join("lnk")
+            .join(name)
+    }
+    /// Compares the link against the golden file and checks its structure.
+    ///
+    /// Set `UPDATE_GOLDEN_FILES` to regenerate them
+    fn assert_golden(link: &ShellLink, name: &str) {
+        let bytes = link.to_bytes();
+        let path = golden_file(name);
+        if std::env::var_os("UPDATE_GOLDEN_FILES").is_some() {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, &bytes).unwrap();
+        }
+        let expected = std::fs::read(&path).expect("Missing golden file");
+        assert_eq!(bytes, expected, "{} does not match", path.display());
+        assert_eq!(parse(&expected), *link, "{} is not valid", path.display());
+    }
+
+    fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes(bytes[offset..offset + 2].try_into().unwrap())
+    }
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+    fn c_string(bytes: &[u8], offset: usize) -> Vec<u8> {
+        bytes[offset..]
+            .iter()
+            .take_while(|b| **b != 0)
+            .copied()
+            .collect()
+    }
+    fn utf16_string(bytes: &[u8], offset: usize) -> String {
+        let units: Vec<u16> = (offset..bytes.len())
+            .step_by(2)
+            .map(|offset| u16_at(bytes, offset))
+            .take_while(|unit| *unit != 0)
+            .collect();
+        String::from_utf16(&units).unwrap()
+    }
+    /// Reads a link following MS-SHLLINK without using the writer's constants
+    fn parse(bytes: &[u8]) -> ShellLink {
+        // ShellLinkHeader
+        assert_eq!(u32_at(bytes, 0), 0x4C, "HeaderSize");
+        let clsid = "01140200-0000-0000-c000-000000000046"
+            .replace('-', "")
+            .as_bytes()
+            .chunks(2)
+            .map(|hex| u8::from_str_radix(std::str::from_utf8(hex).unwrap(), 16).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(&bytes[4..20], clsid, "LinkCLSID");
+        let flags = u32_at(bytes, 20);
+        let has = |flag: u32| flags & flag != 0;
+        // HasLinkTargetIDList is not used
+        assert!(!has(0x1));
+        assert!(has(0x2), "HasLinkInfo");
+        assert!(has(0x80), "IsUnicode");
+        // Only flags the writer knows about
+        assert_eq!(flags & !0xF7, 0, "Unknown LinkFlags {flags:#x}");
+        let icon_index = u32_at(bytes, 56) as i32;
+        let show_command = match u32_at(bytes, 60) {
+            1 => ShowCommand::Normal,
+            3 => ShowCommand::Maximized,
+            7 => ShowCommand::MinimizedNoActive,
+            other => panic!("Invalid ShowCommand {other}"),
+        };
+        assert_eq!(&bytes[64..76], &[0; 12], "HotKey and Reserved");
+
+        // LinkInfo
+        let link_info = &bytes[76..];
+        let link_info_size = u32_at(link_info, 0) as usize;
+        let link_info = &link_info[..link_info_size];
+        let header_size = u32_at(link_info, 4);
+        assert!(header_size >= 0x24, "LinkInfoHeaderSize {header_size:#x}");
+        assert_eq!(u32_at(link_info, 8), 1, "VolumeIDAndLocalBasePath");
+        let volume_id = u32_at(link_info, 12) as usize;
+        assert!(u32_at(link_info, volume_id) > 0x10, "VolumeIDSize");
+        let local_base_path = c_string(link_info, u32_at(link_info, 16) as usize);
+        assert_eq!(u32_at(link_info, 20), 0, "CommonNetworkRelativeLinkOffset");
+        assert!(c_string(link_info, u32_at(link_info, 24) as usize).is_empty());
+        let target = utf16_string(link_info, u32_at(link_info, 28) as usize);
+        assert!(utf16_string(link_info, u32_at(link_info, 32) as usize).is_empty());
+        let ansi: Vec<u8> = target
+            .chars()
+            .map(|c| if c.is_ascii() { c as u8 } else { b'?' })
+            .collect();
+        assert_eq!(local_base_path, ansi, "LocalBasePath");
+
+        // StringData in the order of the spec. The count is in UTF-16 code units
+        let mut offset = 76 + link_info_size;
+        let mut string_data = |flag: u32| {
+            if !has(flag) {
+                return None;
+            }
+            let count = u16_at(bytes, offset) as usize;
+            let units: Vec<u16> = (0..count)
+                .map(|i| u16_at(bytes, offset + 2 + i * 2))
+                .collect();
+            offset += 2 + count * 2;
+            Some(String::from_utf16(&units).unwrap())
+        };
+        let description = string_data(0x4);
+        assert_eq!(string_data(0x8), None, "HasRelativePath");
+        let working_directory = string_data(0x10);
+        let arguments = string_data(0x20);
+        let icon_location = string_data(0x40);
+        // ExtraData ends with a TerminalBlock smaller than 4
+        assert!(u32_at(bytes, offset) < 4, "TerminalBlock");
+        assert_eq!(bytes.len(), offset + 4, "Trailing data");
+        ShellLink {
+            target,
+            arguments,
+            working_directory,
+            description,
+            icon_location,
+            icon_index,
+            show_command,
+        }
+    }
+    /// Splits a command line the way `CommandLineToArgvW` does for arguments after the program name
+    fn command_line_to_argv(command_line: &str) -> Vec<String> {
+        let mut arguments = Vec::new();
+        let mut chars = command_line.chars().peekable();
+        loop {
+            while chars.next_if(|c| *c == ' ' || *c == '\t').is_some() {}
+            if chars.peek().is_none() {
+                return arguments;
+            }
+            let mut argument = String::new();
+            let mut quoted = false;
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => {
+                        let mut backslashes = 1;
+                        while chars.next_if_eq(&'\\').is_some() {
+                            backslashes += 1;
+                        }
+                        if chars.peek() == Some(&'"') {
+                            argument.extend(std::iter::repeat_n('\\', backslashes / 2));
+                            if backslashes % 2 == 1 {
+                                argument.push(chars.next().unwrap());
+                            }
+                        } else {
+                            argument.extend(std::iter::repeat_n('\\', backslashes));
+                        }
+                    }
+                    '"' => quoted = !quoted,
+                    ' ' | '\t' if !quoted => break,
+                    c => argument.push(c),
+                }
+            }
+            arguments.push(argument);
+        }
+    }
+
+    #[test]
+    fn minimal() {
+        let link = ShellLink {
+            target: r"C:\Windows\notepad.exe".to_string(),
+            ..Default::default()
+        };
+        assert_golden(&link, "minimal.lnk");
+    }
+    #[test]
+    fn project() {
+        let link = ShellLink {
+            target: r"C:\Program Files\Microsoft VS Code\Code.exe".to_string(),
+            arguments: Some(r#""C:\Users\Wyatt\Code\auto project""#.to_string()),
+            working_directory: Some(r"C:\Users\Wyatt\Code\auto project".to_string()),
+            description: Some("Open auto project - Code in VS Code".to_string()),
+            icon_location: Some(
+                r"C:\Users\Wyatt\AppData\Roaming\wyatt-herkamp\auto_project\data\auto project - Code.ico"
+                    .to_string(),
+            ),
+            icon_index: 0,
+            show_command: ShowCommand::Maximized,
+        };
+        assert_golden(&link, "project.lnk");
+    }
+    #[test]
+    fn unicode() {
+        let link = ShellLink {
+            target: r"C:\Users\Łukasz\Programy\Edytor.exe".to_string(),
+            arguments: Some(r"C:\Users\Łukasz\Kod\żółw".to_string()),
+            description: Some("Otwórz żółw 🐢".to_string()),
+            ..Default::default()
+        };
+        assert_golden(&link, "unicode.lnk");
+    }
+    #[test]
+    fn header() {
+        let bytes = ShellLink {
+            target: r"C:\a.exe".to_string(),
+            arguments: Some("b".to_string()),
+            ..Default::default()
+        }
+        .to_bytes();
+        assert_eq!(&bytes[0..4], &[0x4C, 0, 0, 0]);
+        // HasLinkInfo, HasArguments and IsUnicode
+        assert_eq!(&bytes[20..24], &[0xA2, 0, 0, 0]);
+        // ShowCommand
+        assert_eq!(&bytes[60..64], &[1, 0, 0, 0]);
+        let link_info_size = u32::from_le_bytes(bytes[76..80].try_into().unwrap()) as usize;
+        let string_data = &bytes[76 + link_info_size..];
+        // Argument "b" followed by the TerminalBlock
+        assert_eq!(string_data, &[1, 0, b'b', 0, 0, 0, 0, 0]);
+    }
+    #[test]
+    fn arguments() {
+        let cases: &[(&[&str], &str)] = &[
+            (&[r"C:\Code\app", "-n"], r"C:\Code\app -n"),
+            (&[r"C:\My Code\app"], r#""C:\My Code\app""#),
+            (&[r"C:\My Code\"], r#""C:\My Code\\""#),
+            (&[r#"say "hi""#], r#""say \"hi\"""#),
+            (&[r#"a\"b"#], r#""a\\\"b""#),
+            (&[r"a\\b"], r"a\\b"),
+            (&["", "x"], r#""" x"#),
+        ];
+        for (arguments, expected) in cases {
+            let arguments: Vec<String> = arguments.iter().map(|a| a.to_string()).collect();
+            let joined = join_arguments(&arguments);
+            assert_eq!(joined, *expected);
+            assert_eq!(command_line_to_argv(&joined), arguments);
+        }
+    }
+    #[test]
+    fn list_lnk_files() {
+        let dir = tempfile::tempdir().unwrap();
+        for file in ["app.lnk", "notes.txt"] {
+            std::fs::write(dir.path().join(file), "").unwrap();
+        }
+        let installed = super::list_lnk_files(dir.path()).unwrap();
+        assert_eq!(installed.len(), 1);
+        assert_eq!(installed[0].name, "app");
+        assert_eq!(installed[0].path, dir.path().join("app.lnk"));
+        assert!(super::list_lnk_files(&dir.path().join("missing"))
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -1,71 +1,128 @@
+use std::{collections::HashSet, path::PathBuf};
+
 use anyhow::Context;
 use clap::Parser;
 use config::Project;
 use console::style;
 use directories::ProjectDirs;
 use human_panic::setup_panic;
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
+use rayon::prelude::*;
 
 use crate::{
-    cli::{AutoProject, Command},
+    cli::{AutoProject, Command, IconsCommand},
     config::Config,
+    discovery::{discover_projects, DiscoveredProject},
+    dry_run::{DryRun, OutputFormat},
+    editor::EditorRules,
+    report::{BuildReport, Stage},
+    shortcuts::{Shortcut, ShortcutManifest, SyncPlan},
     utils::GetConfig,
 };
 pub(crate) mod cli;
 pub(crate) mod config;
+pub(crate) mod discovery;
+pub(crate) mod dry_run;
+pub(crate) mod editor;
+pub(crate) mod icns;
 pub(crate) mod icon;
+pub(crate) mod lnk;
+pub(crate) mod report;
+pub(crate) mod shortcuts;
 pub(crate) mod utils;
 
-#[cfg(not(any(target_os = "windows")))]
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
 compile_error!("Your Platform is not Supported.");
 
+#[cfg(target_os = "linux")]
+mod linux_impl;
 #[cfg(target_os = "windows")]
 mod windows_impl;
-#[cfg(target_os = "windows")]
-use windows_impl::update_shortcuts;
 
 #[derive(Debug)]
 pub struct AppState {
     pub config: Config,
     pub project_dirs: ProjectDirs,
 }
+impl AppState {
+    /// Where the [ShortcutManifest] is stored
+    pub fn manifest_path(&self) -> PathBuf {
+        self.project_dirs.data_dir().join("shortcuts.toml")
+    }
+}
 fn main() -> anyhow::Result<()> {
     setup_panic!();
     pretty_env_logger::init();
     let project_dirs = ProjectDirs::from("dev", "wyatt-herkamp", "auto_project")
         .context("Unable to create project directory")?;
     debug!("Project Directory: {:?}", project_dirs);
+    let cli = AutoProject::parse();
+    if cli.dry_run
+        && !matches!(
+            cli.command,
+            Command::BuildShortcuts | Command::Icons(IconsCommand::Gc)
+        )
+    {
+        return Err(anyhow::anyhow!(
+            "--dry-run is only supported by build-shortcuts and icons gc"
+        ));
+    }
     let config_file = project_dirs.get_config_path();
-    if !config_file.exists() {
+    let config = if config_file.exists() {
+        project_dirs.read_config()?
+    } else if cli.dry_run {
+        // A dry run must not touch the disk
+        debug!("No config file. Using the default config");
+        Config::default()
+    } else {
         return project_dirs.save_default_config();
-    }
-    let config = project_dirs.read_config()?;
+    };
+    config.validate()?;
 
-    if !config.vs_code_path.exists() {
-        info!(
-            "{}",
-            style(format!(
-                "VS Code not found at {}",
-                config.vs_code_path.display()
-            ))
-            .red()
-        );
-        return Ok(());
-    }
     let mut app_state = AppState {
         config,
         project_dirs,
     };
-    let cli = AutoProject::parse();
+    let editor = app_state.config.default_editor();
+    if !matches!(
+        cli.command,
+        Command::DetectEditors(_) | Command::SetVSCodePath { .. }
+    ) && !editor.resolve_executable().exists()
+    {
+        let base_dirs =
+            directories::BaseDirs::new().context("Unable to Locate User Directories?")?;
+        let detected = editor::detect_editors(&base_dirs);
+        if !detected.is_empty() {
+            println!("Found these editors. Pick one with `detect-editors --use <ID>`");
+            cli::print_detected(&detected);
+        }
+        return Err(anyhow::anyhow!(
+            "{} not found at {}",
+            editor.name,
+            editor.executable.display()
+        ));
+    }
     match cli.command {
+        Command::BuildShortcuts if cli.dry_run => dry_run(app_state, cli.format)?,
         Command::BuildShortcuts => build_shortcuts(app_state)?,
         Command::AddProjectsLocation(new_project) => new_project.execute(app_state)?,
+        Command::Icons(IconsCommand::Gc) => icons_gc(app_state, cli.dry_run)?,
+        Command::DetectEditors(detect) => detect.execute(app_state)?,
         Command::AddProject(project) => {
             let project = project.execute(&mut app_state)?;
             info!("Added Project {}", style(&project.name).green());
+            let editor_rules = EditorRules::new(&app_state.config.editor_rules)?;
+            let project = discovery::discover_added_project(&project, &editor_rules);
             let base_dirs =
                 directories::BaseDirs::new().context("Unable to Locate User Directories?")?;
-            update_shortcuts(base_dirs, vec![project], app_state.config)?;
+            let mut backend = app_state.config.shortcut_backend.create(&base_dirs)?;
+            let manifest_path = app_state.manifest_path();
+            let mut manifest = ShortcutManifest::load(&manifest_path)?;
+            manifest.install(
+                backend.as_mut(),
+                &Shortcut::new(&project, &app_state.config),
+            )?;
+            manifest.save(&manifest_path)?;
         }
         Command::AddDisabledProject { path } => {
             let AppState {
@@ -81,6 +138,16 @@ fn main() -> anyhow::Result<()> {
             project_dirs.write_config(&config)?;
             info!("Added Disabled Project {}", style(path.display()).green());
         }
+        Command::AddExcludePattern { pattern } => {
+            let AppState {
+                mut config,
+                project_dirs,
+            } = app_state;
+            globset::Glob::new(&pattern).context("Invalid glob pattern")?;
+            config.exclude.push(pattern.clone());
+            project_dirs.write_config(&config)?;
+            info!("Added Exclude Pattern {}", style(pattern).green());
+        }
         Command::SetVSCodePath { path } => {
             let AppState {
                 mut config,
@@ -107,48 +174,149 @@ fn main() -> anyhow::Result<()> {
     Ok(())
 }
 fn build_shortcuts(app_state: AppState) -> anyhow::Result<()> {
-    let projects = get_projects(&app_state).context("Unable to get projects")?;
+    let mut report = BuildReport::default();
+    let projects = get_projects(&app_state, &mut report);
     for project in &projects {
         info!("{}", style(&project.name).green());
     }
     let base_dirs = directories::BaseDirs::new().context("Unable to Locate User Directories?")?;
-    update_shortcuts(base_dirs, projects, app_state.config)?;
-    Ok(())
+    let mut backend = app_state.config.shortcut_backend.create(&base_dirs)?;
+    let manifest_path = app_state.manifest_path();
+    let mut manifest = ShortcutManifest::load(&manifest_path)?;
+    let shortcuts = projects
+        .iter()
+        .map(|project| Shortcut::new(project, &app_state.config))
+        .collect();
+    let discovery_failed = report
+        .failures
+        .iter()
+        .any(|failure| failure.stage == Stage::Discovery);
+    let plan = SyncPlan::new(backend.as_ref(), &manifest, shortcuts, discovery_failed)?;
+    let summary = plan.apply(backend.as_mut(), &mut manifest, &mut report);
+    manifest.save(&manifest_path)?;
+    info!("Shortcuts: {}", summary);
+    report.finish()
 }
 
-fn get_projects(state: &AppState) -> anyhow::Result<Vec<Project>> {
-    let mut projects = state.config.projects.clone();
-    for project_location in &state.config.project_locations {
-        if !project_location.path.exists() {
+/// Prints everything [build_shortcuts] would do without touching the disk
+fn dry_run(app_state: AppState, format: OutputFormat) -> anyhow::Result<()> {
+    let discovery = discover_projects(&app_state);
+    let base_dirs = directories::BaseDirs::new().context("Unable to Locate User Directories?")?;
+    let backend = app_state.config.shortcut_backend.create(&base_dirs)?;
+    let manifest = ShortcutManifest::load(&app_state.manifest_path())?;
+    let shortcuts = discovery
+        .projects
+        .iter()
+        .map(|discovered| Shortcut::new(&discovered.project, &app_state.config))
+        .collect();
+    let discovery_failed = !discovery.failures.is_empty();
+    let plan = SyncPlan::new(backend.as_ref(), &manifest, shortcuts, discovery_failed)?;
+    DryRun::new(discovery, &plan, backend.as_ref()).print(format)
+}
+/// Removes the icon files that no project uses
+fn icons_gc(app_state: AppState, dry_run: bool) -> anyhow::Result<()> {
+    let discovery = discover_projects(&app_state);
+    if !discovery.failures.is_empty() {
+        for failure in &discovery.failures {
             error!(
-                "Project Location {} does not exist",
-                project_location.path.display()
+                "{} failed during {}: {}",
+                style(&failure.project).red(),
+                failure.stage,
+                failure.error
             );
         }
-        let mut directory = project_location.path.read_dir().context(format!(
-            "Unable to read directory {}",
-            project_location.path.display()
-        ))?;
-        projects.reserve(directory.size_hint().0);
-        while let Some(entry) = directory.next() {
-            let entry = entry.context("Unable to Read Project Folder")?;
-            let path = entry.path();
-            if path.is_dir() {
-                let name = format!(
-                    "{} - {}",
-                    path.file_name().unwrap().to_string_lossy(),
-                    project_location.name.as_deref().unwrap_or("Project")
-                );
-                let icon = icon::build_icon(project_location.icon_style, &name, &state)?;
-                let project = Project {
-                    path,
-                    name,
-                    icon: Some(icon),
-                    ..Default::default()
-                };
-                projects.push(project);
-            }
+        return Err(anyhow::anyhow!(
+            "Not removing any icons because some projects could not be discovered"
+        ));
+    }
+    let unused = icon::unused_icon_files(&discovery.projects, &app_state)?;
+    if dry_run {
+        for file in &unused {
+            println!("{}", file.display());
+        }
+        println!("{} unused icon files", unused.len());
+        return Ok(());
+    }
+    for file in &unused {
+        std::fs::remove_file(file).context(format!("Unable to remove {}", file.display()))?;
+        debug!("Removed {}", file.display());
+    }
+    info!("Removed {} unused icon files", unused.len());
+    Ok(())
+}
+/// Discovers the projects and renders their icons.
+///
+/// A project whose icon fails to render still gets a shortcut without an icon
+fn get_projects(state: &AppState, report: &mut BuildReport) -> Vec<Project> {
+    let discovery = discover_projects(state);
+    for path in &discovery.disabled {
+        info!(
+            "Skipped disabled project {}",
+            style(path.display()).yellow()
+        );
+    }
+    for path in &discovery.unmatched_disabled {
+        warn!(
+            "Disabled project {} does not match any project",
+            style(path.display()).yellow()
+        );
+    }
+    for failure in discovery.failures {
+        error!(
+            "{} failed during {}: {}",
+            style(&failure.project).red(),
+            failure.stage,
+            failure.error
+        );
+        report.failures.push(failure);
+    }
+    if let Err(error) = icon::remove_legacy_cache(state) {
+        warn!("Unable to remove old cached icons: {:#}", error);
+    }
+    // Projects with the same name share their icon files, so only the first of them writes them
+    let mut names = HashSet::new();
+    let writes_icon: Vec<bool> = discovery
+        .projects
+        .iter()
+        .map(|discovered| names.insert(discovered.project.name.clone()))
+        .collect();
+    // Icons are rendered in parallel, failures are recorded in the order of the projects
+    let rendered: Vec<_> = discovery
+        .projects
+        .into_par_iter()
+        .zip(writes_icon)
+        .map(|(discovered, writes_icon)| {
+            let DiscoveredProject {
+                mut project,
+                render_icon,
+                ..
+            } = discovered;
+            let result = if !writes_icon {
+                Ok(())
+            } else if let Some(request) = render_icon {
+                let result = icon::build_icon(&request, &project.name, state).map(|_| ());
+                if result.is_err() {
+                    project.icon = None;
+                }
+                result
+            } else if let Some(icon) = &project.icon {
+                icon::ensure_other_formats(icon, &project.name, state)
+            } else {
+                Ok(())
+            };
+            (project, result)
+        })
+        .collect();
+    let mut projects = Vec::with_capacity(rendered.len());
+    let mut failed_icons = HashSet::new();
+    for (mut project, result) in rendered {
+        if let Err(error) = result {
+            failed_icons.insert(project.name.clone());
+            report.record(project.name.clone(), Stage::Icon, error);
+        } else if failed_icons.contains(&project.name) {
+            project.icon = None;
         }
+        projects.push(project);
     }
-    Ok(projects)
+    projects
 }
@@ -0,0 +1,81 @@
+use std::fmt::Display;
+
+use console::style;
+use log::error;
+use serde::Serialize;
+
+/// The step of building a shortcut that failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Stage {
+    Discovery,
+    Icon,
+    Shortcut,
+}
+impl Display for Stage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Stage::Discovery => write!(f, "discovery"),
+            Stage::Icon => write!(f, "icon"),
+            Stage::Shortcut => write!(f, "shortcut"),
+        }
+    }
+}
+#[derive(Debug, Clone, Serialize)]
+pub struct Failure {
+    /// The name of the project or the path that could not be read
+    pub project: String,
+    pub stage: Stage,
+    pub error: String,
+}
+impl Failure {
+    pub fn new(project: impl Into<String>, stage: Stage, error: anyhow::Error) -> Self {
+        Self {
+            project: project.into(),
+            stage,
+            error: format!("{:#}", error),
+        }
+    }
+}
+
+/// Failures collected while building shortcuts.
+///
+/// A failing project does not stop the other projects from being built
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BuildReport {
+    pub failures: Vec<Failure>,
+}
+impl BuildReport {
+    pub fn record(&mut self, project: impl Into<String>, stage: Stage, error: anyhow::Error) {
+        let failure = Failure::new(project, stage, error);
+        error!(
+            "{} failed during {}: {}",
+            style(&failure.project).red(),
+            failure.stage,
+            failure.error
+        );
+        self.failures.push(failure);
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.failures.is_empty()
+    }
+    /// Prints every failure. Returns an error if anything failed so the exit code is non-zero
+    pub fn finish(self) -> anyhow::Result<()> {
+        if self.is_success() {
+            return Ok(());
+        }
+        eprintln!("{}", style("Failures").red().bold());
+        for failure in &self.failures {
+            eprintln!(
+                "  {} ({}): {}",
+                style(&failure.project).bold(),
+                failure.stage,
+                failure.error
+            );
+        }
+        Err(anyhow::anyhow!(
+            "{} failure(s) while building shortcuts",
+            self.failures.len()
+        ))
+    }
+}
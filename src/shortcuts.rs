@@ -0,0 +1,601 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use directories::BaseDirs;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    config::{Config, Project},
+    icon,
+    lnk::{LnkDirectory, ShowCommand},
+    report::{BuildReport, Stage},
+    utils::{hash_field, to_hex},
+};
+
+/// Everything a backend needs to know to create a shortcut for a project
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Shortcut {
+    /// Name of the shortcut as shown to the user
+    pub name: String,
+    /// The program the shortcut launches
+    pub target: PathBuf,
+    pub arguments: Vec<String>,
+    pub working_directory: PathBuf,
+    pub description: String,
+    pub icon: Option<PathBuf>,
+    /// Name of the icon in the hicolor icon theme. Used by desktop entries instead of [Shortcut::icon]
+    pub icon_name: Option<String>,
+}
+impl Shortcut {
+    pub fn new(project: &Project, config: &Config) -> Self {
+        let editor = config.editor_for(project);
+        let opened = match &project.workspace {
+            Some(workspace) if editor.workspaces == Some(true) => workspace,
+            _ => &project.path,
+        };
+        let description = project
+            .description
+            .clone()
+            .unwrap_or_else(|| format!("Open {} in {}", project.name, editor.name));
+        Self {
+            name: project.name.clone(),
+            target: editor.resolve_executable(),
+            arguments: editor.arguments_for(opened),
+            working_directory: project.path.clone(),
+            description,
+            icon: project.icon.clone(),
+            icon_name: project
+                .icon
+                .as_ref()
+                .filter(|_| config.icon_theme)
+                .map(|_| icon::theme_icon_name(&project.name)),
+        }
+    }
+    /// A hash of everything that ends up inside of the shortcut.
+    ///
+    /// Used to tell if an installed shortcut is out of date
+    pub fn hash(&self) -> String {
+        let mut hasher = Sha256::new();
+        let mut field = |value: &[u8]| hash_field(&mut hasher, value);
+        field(self.target.as_os_str().as_encoded_bytes());
+        field(&(self.arguments.len() as u64).to_le_bytes());
+        for argument in &self.arguments {
+            field(argument.as_bytes());
+        }
+        field(self.working_directory.as_os_str().as_encoded_bytes());
+        field(self.description.as_bytes());
+        field(
+            self.icon
+                .as_ref()
+                .map(|icon| icon.as_os_str().as_encoded_bytes())
+                .unwrap_or_default(),
+        );
+        field(self.icon_name.as_deref().unwrap_or_default().as_bytes());
+        to_hex(&hasher.finalize())
+    }
+}
+/// A shortcut that currently exists in a backend
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstalledShortcut {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+/// A place shortcuts can be created in. Such as the Windows Start Menu
+///
+/// Creating a backend must not touch the disk. So a plan can be made without changing anything
+pub trait ShortcutBackend {
+    /// Where the shortcut would be installed
+    fn shortcut_path(&self, shortcut: &Shortcut) -> PathBuf;
+
+    /// Creates the shortcut. Replacing any existing shortcut with the same name
+    fn install(&mut self, shortcut: &Shortcut) -> anyhow::Result<InstalledShortcut>;
+
+    fn remove(&mut self, shortcut: &InstalledShortcut) -> anyhow::Result<()>;
+    /// Lists all shortcuts created by auto_project
+    fn list_installed(&self) -> anyhow::Result<Vec<InstalledShortcut>>;
+}
+
+/// Which [ShortcutBackend] to use
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ShortcutBackendConfig {
+    /// Shortcuts inside of the Windows Start Menu
+    StartMenu,
+    /// XDG Desktop Entries
+    DesktopEntry,
+    /// .lnk files written to a directory. Works on every platform
+    LnkDirectory {
+        path: PathBuf,
+        #[serde(default)]
+        show_command: ShowCommand,
+    },
+}
+impl Default for ShortcutBackendConfig {
+    fn default() -> Self {
+        if cfg!(target_os = "windows") {
+            Self::StartMenu
+        } else {
+            Self::DesktopEntry
+        }
+    }
+}
+impl ShortcutBackendConfig {
+    pub fn create(&self, base: &BaseDirs) -> anyhow::Result<Box<dyn ShortcutBackend>> {
+        match self {
+            Self::LnkDirectory { path, show_command } => {
+                Ok(Box::new(LnkDirectory::new(path.clone(), *show_command)?))
+            }
+            #[cfg(target_os = "windows")]
+            Self::StartMenu => Ok(Box::new(crate::windows_impl::StartMenu::new(base)?)),
+            #[cfg(target_os = "linux")]
+            Self::DesktopEntry => Ok(Box::new(crate::linux_impl::DesktopEntries::new(base)?)),
+            #[allow(unreachable_patterns)]
+            other => Err(anyhow::anyhow!(
+                "The {:?} backend is not supported on this platform",
+                other
+            )),
+        }
+    }
+}
+
+/// The shortcuts auto_project has created. Anything not in here is left alone
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ShortcutManifest {
+    #[serde(default)]
+    pub shortcuts: BTreeMap<String, ManifestEntry>,
+}
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub path: PathBuf,
+    /// [Shortcut::hash] of the shortcut when it was installed
+    pub hash: String,
+}
+impl ShortcutManifest {
+    /// Reads the manifest. A missing file is an empty manifest
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let manifest = std::fs::read_to_string(path).context("Unable to read shortcut manifest")?;
+        toml::from_str(&manifest).context("Unable to parse shortcut manifest")
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).context("Unable to create data directory")?;
+        }
+        let manifest = toml::to_string_pretty(self).context("Unable to serialize manifest")?;
+        std::fs::write(path, manifest).context("Unable to write shortcut manifest")?;
+        debug!("Shortcut Manifest Updated at {}", path.display());
+        Ok(())
+    }
+    /// Installs a single shortcut and records it
+    pub fn install(
+        &mut self,
+        backend: &mut dyn ShortcutBackend,
+        shortcut: &Shortcut,
+    ) -> anyhow::Result<InstalledShortcut> {
+        let installed = backend.install(shortcut)?;
+        if let Some(old) = self.shortcuts.get(&shortcut.name) {
+            if old.path != installed.path {
+                let old = InstalledShortcut {
+                    name: shortcut.name.clone(),
+                    path: old.path.clone(),
+                };
+                if let Err(error) = backend.remove(&old) {
+                    warn!("Unable to remove {}: {:#}", old.path.display(), error);
+                }
+            }
+        }
+        self.shortcuts.insert(
+            shortcut.name.clone(),
+            ManifestEntry {
+                path: installed.path.clone(),
+                hash: shortcut.hash(),
+            },
+        );
+        Ok(installed)
+    }
+}
+/// The changes needed to bring the backend in line with the wanted shortcuts
+#[derive(Debug, Clone, Default)]
+pub struct SyncPlan {
+    pub create: Vec<Shortcut>,
+    /// The shortcut exists but its target, arguments or icon changed
+    pub update: Vec<Shortcut>,
+    /// Shortcuts we created before that are no longer wanted
+    pub remove: Vec<InstalledShortcut>,
+    pub unchanged: Vec<Shortcut>,
+    /// Shortcuts in the manifest that no longer exist. They are dropped from the manifest
+    pub missing: Vec<String>,
+    /// Shortcuts that are no longer wanted but kept because discovery failed
+    pub kept: Vec<InstalledShortcut>,
+}
+impl SyncPlan {
+    /// Compares the wanted shortcuts against the manifest.
+    ///
+    /// If `discovery_failed` nothing is removed. The projects of a location that could not be read are still wanted
+    pub fn new(
+        backend: &dyn ShortcutBackend,
+        manifest: &ShortcutManifest,
+        shortcuts: Vec<Shortcut>,
+        discovery_failed: bool,
+    ) -> anyhow::Result<Self> {
+        let installed: HashSet<PathBuf> = backend
+            .list_installed()?
+            .into_iter()
+            .map(|installed| installed.path)
+            .collect();
+        let mut plan = Self::default();
+        let mut wanted = HashSet::new();
+        for shortcut in shortcuts {
+            if !wanted.insert(shortcut.name.clone()) {
+                warn!(
+                    "Multiple projects are named {}. Only the first one will get a shortcut",
+                    shortcut.name
+                );
+                continue;
+            }
+            match manifest.shortcuts.get(&shortcut.name) {
+                Some(entry) if !installed.contains(&entry.path) => plan.create.push(shortcut),
+                // Moved, such as after the file names of a backend changed
+                Some(entry) if entry.path != backend.shortcut_path(&shortcut) => {
+                    plan.update.push(shortcut)
+                }
+                Some(entry) if entry.hash != shortcut.hash() => plan.update.push(shortcut),
+                Some(_) => plan.unchanged.push(shortcut),
+                None => plan.create.push(shortcut),
+            }
+        }
+        for (name, entry) in &manifest.shortcuts {
+            if wanted.contains(name) {
+                continue;
+            }
+            if installed.contains(&entry.path) {
+                let shortcut = InstalledShortcut {
+                    name: name.clone(),
+                    path: entry.path.clone(),
+                };
+                if discovery_failed {
+                    plan.kept.push(shortcut);
+                } else {
+                    plan.remove.push(shortcut);
+                }
+            } else {
+                plan.missing.push(name.clone());
+            }
+        }
+        if !plan.kept.is_empty() {
+            warn!(
+                "Keeping {} shortcuts that were not found because discovery failed",
+                plan.kept.len()
+            );
+        }
+        Ok(plan)
+    }
+    /// Applies the plan. The manifest is updated as each change is made.
+    ///
+    /// A shortcut that fails is recorded in the report and does not stop the others
+    pub fn apply(
+        self,
+        backend: &mut dyn ShortcutBackend,
+        manifest: &mut ShortcutManifest,
+        report: &mut BuildReport,
+    ) -> SyncSummary {
+        let mut summary = SyncSummary {
+            unchanged: self.unchanged.len(),
+            ..Default::default()
+        };
+        for name in &self.missing {
+            manifest.shortcuts.remove(name);
+        }
+        for shortcut in self.remove {
+            debug!("Removing shortcut {}", shortcut.path.display());
+            match backend.remove(&shortcut) {
+                Ok(()) => {
+                    manifest.shortcuts.remove(&shortcut.name);
+                    summary.removed += 1;
+                }
+                Err(error) => report.record(shortcut.name, Stage::Shortcut, error),
+            }
+        }
+        let changes = (self.create.iter().map(|shortcut| (shortcut, false)))
+            .chain(self.update.iter().map(|shortcut| (shortcut, true)));
+        for (shortcut, is_update) in changes {
+            debug!(
+                "Creating Shortcut to {} at {}",
+                shortcut.name,
+                shortcut.working_directory.display()
+            );
+            match manifest.install(backend, shortcut) {
+                Ok(_) if is_update => summary.updated += 1,
+                Ok(_) => summary.created += 1,
+                Err(error) => report.record(shortcut.name.clone(), Stage::Shortcut, error),
+            }
+        }
+        summary
+    }
+
+    pub fn summary(&self) -> SyncSummary {
+        SyncSummary {
+            created: self.create.len(),
+            updated: self.update.len(),
+            removed: self.remove.len(),
+            unchanged: self.unchanged.len(),
+        }
+    }
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct SyncSummary {
+    pub created: usize,
+    pub updated: usize,
+    pub removed: usize,
+    pub unchanged: usize,
+}
+impl Display for SyncSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} created, {} updated, {} removed, {} unchanged",
+            self.created, self.updated, self.removed, self.unchanged
+        )
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{collections::BTreeMap, path::PathBuf};
+
+    use super::{InstalledShortcut, Shortcut, ShortcutBackend, ShortcutManifest, SyncPlan};
+    use crate::{
+        config::{Config, Project},
+        report::BuildReport,
+    };
+
+    /// Keeps shortcuts in memory. Used to test the code that drives the backends
+    #[derive(Debug, Default)]
+    pub struct MemoryBackend {
+        pub shortcuts: BTreeMap<String, Shortcut>,
+        /// Names of shortcuts that fail to install
+        pub failing: Vec<String>,
+    }
+    impl MemoryBackend {
+        fn path(name: &str) -> PathBuf {
+            PathBuf::from("memory").join(name)
+        }
+    }
+    impl ShortcutBackend for MemoryBackend {
+        fn shortcut_path(&self, shortcut: &Shortcut) -> PathBuf {
+            Self::path(&shortcut.name)
+        }
+
+        fn install(&mut self, shortcut: &Shortcut) -> anyhow::Result<InstalledShortcut> {
+            if self.failing.contains(&shortcut.name) {
+                return Err(anyhow::anyhow!("Unable to install {}", shortcut.name));
+            }
+            self.shortcuts
+                .insert(shortcut.name.clone(), shortcut.clone());
+            Ok(InstalledShortcut {
+                name: shortcut.name.clone(),
+                path: Self::path(&shortcut.name),
+            })
+        }
+
+        fn remove(&mut self, shortcut: &InstalledShortcut) -> anyhow::Result<()> {
+            self.shortcuts.remove(&shortcut.name);
+            Ok(())
+        }
+
+        fn list_installed(&self) -> anyhow::Result<Vec<InstalledShortcut>> {
+            Ok(self
+                .shortcuts
+                .keys()
+                .map(|name| InstalledShortcut {
+                    name: name.clone(),
+                    path: Self::path(name),
+                })
+                .collect())
+        }
+    }
+
+    pub fn project(name: &str) -> Project {
+        Project {
+            path: PathBuf::from("/code").join(name),
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn plan(
+        backend: &MemoryBackend,
+        manifest: &ShortcutManifest,
+        projects: &[Project],
+        config: &Config,
+    ) -> SyncPlan {
+        let shortcuts = projects
+            .iter()
+            .map(|project| Shortcut::new(project, config))
+            .collect();
+        SyncPlan::new(backend, manifest, shortcuts, false).unwrap()
+    }
+
+    #[test]
+    fn sync() {
+        let config = Config::default();
+        let mut backend = MemoryBackend::default();
+        let mut manifest = ShortcutManifest::default();
+        // A shortcut the user created themselves
+        backend
+            .install(&Shortcut::new(&project("manual"), &config))
+            .unwrap();
+
+        let summary = plan(&backend, &manifest, &[project("a"), project("b")], &config).apply(
+            &mut backend,
+            &mut manifest,
+            &mut BuildReport::default(),
+        );
+        assert_eq!(
+            summary.to_string(),
+            "2 created, 0 updated, 0 removed, 0 unchanged"
+        );
+        assert_eq!(
+            manifest.shortcuts.keys().collect::<Vec<_>>(),
+            vec!["a", "b"]
+        );
+
+        let mut b = project("b");
+        b.description = Some("Changed".to_string());
+        let summary = plan(&backend, &manifest, &[b, project("c")], &config).apply(
+            &mut backend,
+            &mut manifest,
+            &mut BuildReport::default(),
+        );
+        assert_eq!(
+            summary.to_string(),
+            "1 created, 1 updated, 1 removed, 0 unchanged"
+        );
+        assert_eq!(
+            backend.shortcuts.keys().collect::<Vec<_>>(),
+            vec!["b", "c", "manual"]
+        );
+        assert_eq!(backend.shortcuts["b"].description, "Changed");
+        let c = &backend.shortcuts["c"];
+        assert_eq!(c.target, config.vs_code_path);
+        assert_eq!(c.arguments, vec!["/code/c".to_string()]);
+        assert_eq!(c.description, "Open c in VS Code");
+
+        // A shortcut that was deleted by the user is created again
+        backend.shortcuts.remove("c");
+        let mut b = project("b");
+        b.description = Some("Changed".to_string());
+        let summary = plan(&backend, &manifest, &[b, project("c")], &config).summary();
+        assert_eq!(
+            summary.to_string(),
+            "1 created, 0 updated, 0 removed, 1 unchanged"
+        );
+    }
+    #[test]
+    fn sync_discovery_failure() {
+        let config = Config::default();
+        let mut backend = MemoryBackend::default();
+        let mut manifest = ShortcutManifest::default();
+        let projects = [project("a"), project("b")];
+        plan(&backend, &manifest, &projects, &config).apply(
+            &mut backend,
+            &mut manifest,
+            &mut BuildReport::default(),
+        );
+        // The location of b could not be read
+        let shortcuts = vec![Shortcut::new(&project("a"), &config)];
+        let plan = SyncPlan::new(&backend, &manifest, shortcuts, true).unwrap();
+        assert!(plan.remove.is_empty());
+        assert_eq!(plan.kept.len(), 1);
+        assert_eq!(plan.kept[0].name, "b");
+        let summary = plan.apply(&mut backend, &mut manifest, &mut BuildReport::default());
+        assert_eq!(
+            summary.to_string(),
+            "0 created, 0 updated, 0 removed, 1 unchanged"
+        );
+        assert_eq!(backend.shortcuts.keys().collect::<Vec<_>>(), vec!["a", "b"]);
+        assert!(manifest.shortcuts.contains_key("b"));
+    }
+    #[test]
+    fn sync_failure() {
+        let config = Config::default();
+        let mut backend = MemoryBackend {
+            failing: vec!["b".to_string()],
+            ..Default::default()
+        };
+        let mut manifest = ShortcutManifest::default();
+        let mut report = BuildReport::default();
+        let summary = plan(
+            &backend,
+            &manifest,
+            &[project("a"), project("b"), project("c")],
+            &config,
+        )
+        .apply(&mut backend, &mut manifest, &mut report);
+        assert_eq!(summary.created, 2);
+        assert_eq!(
+            manifest.shortcuts.keys().collect::<Vec<_>>(),
+            vec!["a", "c"]
+        );
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].project, "b");
+        assert!(report.finish().is_err());
+    }
+    #[test]
+    fn editor() {
+        let mut config = Config {
+            editor: "subl".to_string(),
+            ..Default::default()
+        };
+        let shortcut = Shortcut::new(&project("a"), &config);
+        assert_eq!(shortcut.description, "Open a in Sublime Text");
+        assert_eq!(shortcut.arguments, ["--new-window", "/code/a"]);
+
+        config.editors.push(crate::editor::Editor {
+            id: "subl".to_string(),
+            name: "Sublime".to_string(),
+            executable: PathBuf::from("/opt/sublime_text/sublime_text"),
+            arguments: vec!["{path}".to_string()],
+            workspaces: None,
+        });
+        let shortcut = Shortcut::new(&project("a"), &config);
+        assert_eq!(shortcut.description, "Open a in Sublime");
+        assert_eq!(
+            shortcut.target,
+            PathBuf::from("/opt/sublime_text/sublime_text")
+        );
+        assert_eq!(shortcut.arguments, ["/code/a"]);
+
+        let mut workspace = project("workspace");
+        workspace.workspace = Some(PathBuf::from("/code/workspace/app.code-workspace"));
+        assert_eq!(
+            Shortcut::new(&workspace, &config).arguments,
+            ["/code/workspace"]
+        );
+        workspace.editor = Some("code".to_string());
+        assert_eq!(
+            Shortcut::new(&workspace, &config).arguments,
+            ["/code/workspace/app.code-workspace"]
+        );
+        // Replacing the preset keeps its workspace support
+        config.editors.push(crate::editor::Editor {
+            id: "code".to_string(),
+            name: "Code".to_string(),
+            executable: PathBuf::from("/opt/code/code"),
+            arguments: vec!["{path}".to_string()],
+            workspaces: None,
+        });
+        assert_eq!(
+            Shortcut::new(&workspace, &config).arguments,
+            ["/code/workspace/app.code-workspace"]
+        );
+        config.editors[1].workspaces = Some(false);
+        assert_eq!(
+            Shortcut::new(&workspace, &config).arguments,
+            ["/code/workspace"]
+        );
+
+        let mut java = project("java");
+        java.editor = Some("idea".to_string());
+        let shortcut = Shortcut::new(&java, &config);
+        assert_eq!(shortcut.description, "Open java in IntelliJ IDEA");
+        assert!(config.validate().is_ok());
+
+        config.editor = "missing".to_string();
+        assert!(config.validate().is_err());
+        config.editor = "code".to_string();
+        java.editor = Some("missing".to_string());
+        config.projects.push(java);
+        assert!(config.validate().is_err());
+    }
+}
@@ -4,9 +4,37 @@ use anyhow::Context;
 use console::style;
 use directories::ProjectDirs;
 use log::{debug, info};
+use sha2::{Digest, Sha256};
 
 use crate::config::Config;
 
+/// Lowercase hex encoding of the bytes
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+/// The name with everything except ASCII letters, digits, `-` and `_` replaced by `_`.
+///
+/// Followed by a short hash of the name, so names that only differ in the replaced characters stay unique
+pub fn unique_slug(name: &str) -> String {
+    let slug: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let hash = Sha256::digest(name.as_bytes());
+    format!("{}-{}", slug, to_hex(&hash[..4]))
+}
+/// Adds the value to the hash prefixed by its length, so values can not run into each other
+pub fn hash_field(hasher: &mut Sha256, value: &[u8]) {
+    hasher.update((value.len() as u64).to_le_bytes());
+    hasher.update(value);
+}
+
 pub trait GetConfig {
     fn get_config_path(&self) -> PathBuf;
 
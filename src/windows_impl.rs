@@ -1,4 +1,9 @@
-use std::{ffi::CString, iter::once, path::Path, sync::Once};
+use std::{
+    ffi::CString,
+    iter::once,
+    path::{Path, PathBuf},
+    sync::Once,
+};
 
 use anyhow::{anyhow, Context};
 use directories::BaseDirs;
@@ -15,53 +20,59 @@ use windows::{
     },
 };
 
-use crate::{Config, Project};
-pub fn update_shortcuts(
-    base: BaseDirs,
-    projects: Vec<Project>,
-    config: Config,
-) -> anyhow::Result<()> {
-    let start_menu = base
-        .config_dir()
-        .join("Microsoft")
-        .join("Windows")
-        .join("Start Menu")
-        .join("Programs");
-    if !start_menu.exists() {
-        return Err(anyhow!("Start Menu does not exist"));
-    }
-    initialize_com();
+use crate::{
+    lnk::{self, join_arguments},
+    shortcuts::{InstalledShortcut, Shortcut, ShortcutBackend},
+};
 
-    let programming_folder = start_menu.join("Programming Projects");
-    if !programming_folder.exists() {
-        std::fs::create_dir_all(&programming_folder)?;
-    } else {
-        std::fs::remove_dir_all(&programming_folder)?;
-        std::fs::create_dir_all(&programming_folder)?;
-    }
-    debug!("Putting shortcuts in {}", programming_folder.display());
+/// Creates shortcuts in the "Programming Projects" folder of the Start Menu
+#[derive(Debug)]
+pub struct StartMenu {
+    programming_folder: PathBuf,
+}
+impl StartMenu {
+    pub fn new(base: &BaseDirs) -> anyhow::Result<Self> {
+        let start_menu = base
+            .config_dir()
+            .join("Microsoft")
+            .join("Windows")
+            .join("Start Menu")
+            .join("Programs");
+        if !start_menu.exists() {
+            return Err(anyhow!("Start Menu does not exist"));
+        }
+        initialize_com();
 
-    let vs_code = path_to_c_string(&config.vs_code_path)?;
-    for project in projects {
-        debug!(
-            "Creating Shortcut to {} at {}",
-            project.name,
-            project.path.display()
-        );
+        let programming_folder = start_menu.join("Programming Projects");
+        debug!("Putting shortcuts in {}", programming_folder.display());
+        Ok(Self { programming_folder })
+    }
+}
+impl ShortcutBackend for StartMenu {
+    fn shortcut_path(&self, shortcut: &Shortcut) -> PathBuf {
+        lnk::lnk_path(&self.programming_folder, shortcut)
+    }
 
-        let project_dir = path_to_c_string(&project.path)?;
-        let link_path = programming_folder.join(format!("{}.lnk", project.name));
-        let description = CString::new(format!("Open {} in VS Code", project.name))
-            .context("Unable to create description")?;
+    fn install(&mut self, shortcut: &Shortcut) -> anyhow::Result<InstalledShortcut> {
+        if !self.programming_folder.exists() {
+            std::fs::create_dir_all(&self.programming_folder)?;
+        }
+        let target = path_to_c_string(&shortcut.target)?;
+        let arguments = CString::new(join_arguments(&shortcut.arguments))
+            .context("Unable to create arguments")?;
+        let working_directory = path_to_c_string(&shortcut.working_directory)?;
+        let description =
+            CString::new(shortcut.description.as_str()).context("Unable to create description")?;
+        let link_path = self.shortcut_path(shortcut);
 
         unsafe {
             let shell_link: IShellLinkA = CoCreateInstance(&ShellLink, None, CLSCTX_INPROC_SERVER)?;
-            shell_link.SetPath(PCSTR(vs_code.as_ptr().cast()))?;
-            shell_link.SetArguments(PCSTR(project_dir.as_ptr().cast()))?;
+            shell_link.SetPath(PCSTR(target.as_ptr().cast()))?;
+            shell_link.SetArguments(PCSTR(arguments.as_ptr().cast()))?;
             shell_link.SetDescription(PCSTR(description.as_ptr().cast()))?;
-            shell_link.SetWorkingDirectory(PCSTR(project_dir.as_ptr().cast()))?;
+            shell_link.SetWorkingDirectory(PCSTR(working_directory.as_ptr().cast()))?;
             shell_link.SetShowCmd(SW_HIDE)?;
-            if let Some(icon) = project.icon {
+            if let Some(icon) = &shortcut.icon {
                 let icon = path_to_c_string(icon)?;
                 shell_link.SetIconLocation(PCSTR(icon.as_ptr().cast()), 0)?;
             }
@@ -70,10 +81,20 @@ pub fn update_shortcuts(
                 TRUE,
             )?;
         }
+        Ok(InstalledShortcut {
+            name: shortcut.name.clone(),
+            path: link_path,
+        })
     }
-    Ok(())
-}
 
+    fn remove(&mut self, shortcut: &InstalledShortcut) -> anyhow::Result<()> {
+        lnk::remove_lnk(shortcut)
+    }
+
+    fn list_installed(&self) -> anyhow::Result<Vec<InstalledShortcut>> {
+        lnk::list_lnk_files(&self.programming_folder)
+    }
+}
 /// Converts a Path to a CString.
 ///
 /// Path must be UTF-8
@@ -88,7 +109,7 @@ fn path_to_c_string(path: impl AsRef<Path>) -> anyhow::Result<CString> {
     ))
 }
 pub fn string_to_os_utf16(string: &str) -> Vec<u16> {
-    return string.encode_utf16().chain(once(0)).collect::<Vec<u16>>();
+    string.encode_utf16().chain(once(0)).collect::<Vec<u16>>()
 }
 static CO_INITIALIZE_ONCE: Once = Once::new();
 